@@ -0,0 +1,76 @@
+//! Per-request latency of `RedisLimiter::use_key` with a new connection for every request, which
+//! is what the limiter used to do, compared with a limiter sharing its connection manager.
+//!
+//! Start Redis with `docker compose up redis` and run
+//! `REDIS_URI=redis://127.0.0.1:6479 cargo bench --bench redis_limiter`.
+
+use std::time::{Duration, Instant};
+
+use apikeys_rs::{
+    limiters::redis_limiter::RedisLimiter,
+    schema::CURRENT_SCHEMA_VERSION,
+    traits::ApiKeyLimiter,
+    types::{ApiKey, ApiKeyLimit, ApiKeyLimits, ApiKeyRestrictions, ApiKeyStatus},
+};
+
+const ITERATIONS: usize = 2_000;
+
+fn bench_api_key() -> ApiKey {
+    ApiKey {
+        key: "bench_key".to_string(),
+        limits: ApiKeyLimits {
+            max_reads_per_minute: ApiKeyLimit::Limited(u32::MAX),
+            max_writes_per_minute: ApiKeyLimit::Unlimited,
+            max_concurrent_requests: ApiKeyLimit::Unlimited,
+        },
+        restrictions: ApiKeyRestrictions { allowed_domains: vec![] },
+        status: ApiKeyStatus::Active,
+        owner: None,
+        created_at: chrono::Utc::now(),
+        updated_at: chrono::Utc::now(),
+        schema_version: CURRENT_SCHEMA_VERSION,
+    }
+}
+
+fn report(name: &str, mut samples: Vec<Duration>) {
+    samples.sort();
+
+    let total: Duration = samples.iter().sum();
+    let percentile = |p: usize| samples[(samples.len() * p / 100).min(samples.len() - 1)];
+
+    println!(
+        "{name:<28} mean {:>10.1?}  p50 {:>10.1?}  p99 {:>10.1?}",
+        total / samples.len() as u32,
+        percentile(50),
+        percentile(99)
+    );
+}
+
+#[tokio::main]
+async fn main() {
+    let Ok(uri) = std::env::var("REDIS_URI") else {
+        eprintln!("REDIS_URI is not set, skipping the redis limiter benchmark");
+        return;
+    };
+
+    let api_key = bench_api_key();
+
+    let mut samples = Vec::with_capacity(ITERATIONS);
+    for _ in 0..ITERATIONS {
+        let started_at = Instant::now();
+        let limiter = RedisLimiter::new(&uri).await.expect("Unable to create redis limiter");
+        limiter.use_key(&api_key).await.expect("The key should not be rate limited");
+        samples.push(started_at.elapsed());
+    }
+    report("connection per request", samples);
+
+    let limiter = RedisLimiter::new(&uri).await.expect("Unable to create redis limiter");
+
+    let mut samples = Vec::with_capacity(ITERATIONS);
+    for _ in 0..ITERATIONS {
+        let started_at = Instant::now();
+        limiter.use_key(&api_key).await.expect("The key should not be rate limited");
+        samples.push(started_at.elapsed());
+    }
+    report("shared connection manager", samples);
+}
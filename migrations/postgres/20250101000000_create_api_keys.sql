@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS api_keys (
+    id BIGSERIAL PRIMARY KEY,
+    key TEXT NOT NULL,
+    key_hash TEXT NOT NULL,
+    limits JSONB NOT NULL,
+    restrictions JSONB NOT NULL,
+    status TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS api_keys_key_hash_idx ON api_keys (key_hash);
+
+CREATE INDEX IF NOT EXISTS api_keys_status_idx ON api_keys (status);
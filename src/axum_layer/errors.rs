@@ -11,6 +11,7 @@ use serde::Serialize;
 use crate::errors::{ApiKeyLimiterError, ApiKeyStorageError};
 
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum ApiKeyLayerError {
     MissingApiKey,
     InvalidApiKey,
@@ -30,9 +31,9 @@ impl fmt::Display for ApiKeyLayerError {
             ApiKeyLayerError::DomainNotAllowed => {
                 write!(f, "The provided API key is not allowed for this domain")
             }
-            ApiKeyLayerError::LimiterError(e) => write!(f, "Limiter error: {}", e),
+            ApiKeyLayerError::LimiterError(_) => write!(f, "Limiter error"),
             ApiKeyLayerError::UnexpectedError => write!(f, "Unexpected error"),
-            ApiKeyLayerError::StorageError(e) => write!(f, "Storage error: {}", e),
+            ApiKeyLayerError::StorageError(_) => write!(f, "Storage error"),
         }
     }
 }
@@ -51,7 +52,15 @@ impl ApiKeyLayerError {
     }
 }
 
-impl std::error::Error for ApiKeyLayerError {}
+impl std::error::Error for ApiKeyLayerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ApiKeyLayerError::LimiterError(e) => Some(e),
+            ApiKeyLayerError::StorageError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
 
 impl From<ApiKeyLayerError> for ApiKeyErrorResponse {
     fn from(error: ApiKeyLayerError) -> Self {
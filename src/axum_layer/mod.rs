@@ -9,10 +9,23 @@ use http::HeaderMap;
 use tower::{Layer, Service};
 
 pub mod errors;
+mod release;
+pub mod usage;
 use tracing::error;
 
-use self::errors::ApiKeyLayerError;
-use crate::{errors::ApiKeyManagerError, traits::ApiKeyManager};
+use self::{
+    errors::ApiKeyLayerError,
+    release::{release_when_finished, ReleaseGuard},
+    usage::{response_cost, settle_usage},
+};
+use crate::{
+    errors::ApiKeyManagerError,
+    traits::ApiKeyManager,
+    types::{ApiKey, ApiKeyLimit, Lease},
+};
+
+/// Header the key is read from.
+pub const API_KEY_HEADER: &str = "x-api-key";
 
 #[derive(Clone)]
 pub struct ApiKeyLayer<T>
@@ -61,7 +74,7 @@ where
         let headers = request.headers().clone();
         // let origin = extract_header(header::ORIGIN.as_str(), &headers);
 
-        let x_api_key = match extract_header("x-api-key", &headers) {
+        let x_api_key = match extract_header(API_KEY_HEADER, &headers) {
             Some(key) => key,
             None => {
                 return Box::pin(async move {
@@ -73,16 +86,26 @@ where
 
         let manager = self.manager.clone();
         let future = self.inner.call(request);
-        let verification_future = verify_api_key(manager, x_api_key);
         Box::pin(async move {
-            match verification_future.await {
-                Ok(true) => {
+            match verify_api_key(&manager, x_api_key).await {
+                Ok((api_key, lease)) => {
+                    // Held until the response body is finished so that streaming responses keep their slot.
+                    let release_guard = match api_key.limits.max_concurrent_requests {
+                        ApiKeyLimit::Limited(_) => {
+                            Some(ReleaseGuard::new(manager.clone(), api_key.clone(), lease.clone()))
+                        }
+                        ApiKeyLimit::Unlimited => None,
+                    };
+
                     let response: Response = future.await?;
-                    Ok(response)
-                }
-                Ok(false) => {
-                    let response = errors::ApiKeyLayerError::InvalidApiKey.into_response();
-                    Ok(response)
+                    if let Some(cost) = response_cost(&response) {
+                        settle_usage(&manager, &api_key, &lease, cost).await;
+                    }
+
+                    match release_guard {
+                        Some(release_guard) => Ok(release_when_finished(response, release_guard)),
+                        None => Ok(response),
+                    }
                 }
                 Err(e) => {
                     let response = e.into_response();
@@ -116,17 +139,13 @@ fn extract_header(key: &str, headers: &HeaderMap) -> Option<String> {
 }
 
 async fn verify_api_key(
-    manager: impl ApiKeyManager + Send + Sync,
+    manager: &(impl ApiKeyManager + Send + Sync),
     key: String,
-) -> Result<bool, errors::ApiKeyLayerError> {
-    match manager.use_key(key.as_str()).await {
-        Ok(key) => key,
-        Err(e) => {
-            return Err(e.into());
-        }
-    };
-
-    Ok(true)
+) -> Result<(ApiKey, Lease), errors::ApiKeyLayerError> {
+    match manager.acquire_key(key.as_str()).await {
+        Ok(acquired) => Ok(acquired),
+        Err(e) => Err(e.into()),
+    }
 }
 
 impl From<ApiKeyManagerError> for ApiKeyLayerError {
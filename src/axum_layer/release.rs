@@ -0,0 +1,85 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use axum::{
+    body::{Body, Bytes},
+    response::Response,
+};
+use http_body::{Frame, SizeHint};
+use tracing::error;
+
+use crate::{
+    traits::ApiKeyManager,
+    types::{ApiKey, Lease},
+};
+
+/// Releases the in-flight slot held by a request when dropped.
+pub(crate) struct ReleaseGuard<T>
+where
+    T: ApiKeyManager + Send + Sync + 'static,
+{
+    pending: Option<(T, ApiKey, Lease)>,
+}
+
+impl<T> ReleaseGuard<T>
+where
+    T: ApiKeyManager + Send + Sync + 'static,
+{
+    pub(crate) fn new(manager: T, api_key: ApiKey, lease: Lease) -> Self {
+        Self { pending: Some((manager, api_key, lease)) }
+    }
+}
+
+impl<T> Drop for ReleaseGuard<T>
+where
+    T: ApiKeyManager + Send + Sync + 'static,
+{
+    fn drop(&mut self) {
+        let Some((manager, api_key, lease)) = self.pending.take() else {
+            return;
+        };
+
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => {
+                handle.spawn(async move {
+                    if let Err(e) = manager.release_key(&api_key, &lease).await {
+                        error!("Unable to release the in-flight request: {e:?}");
+                    }
+                });
+            }
+            Err(e) => error!("Unable to release the in-flight request: {e}"),
+        }
+    }
+}
+
+/// Response body that holds a `ReleaseGuard` until the body has been sent or dropped.
+struct ReleasingBody {
+    inner: Body,
+    _guard: Box<dyn Send>,
+}
+
+impl http_body::Body for ReleasingBody {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Frame<Bytes>, axum::Error>>> {
+        Pin::new(&mut self.inner).poll_frame(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+pub(crate) fn release_when_finished<T>(response: Response, guard: ReleaseGuard<T>) -> Response
+where
+    T: ApiKeyManager + Send + Sync + 'static,
+{
+    response.map(|inner| Body::new(ReleasingBody { inner, _guard: Box::new(guard) }))
+}
@@ -0,0 +1,44 @@
+use std::cmp::Ordering;
+
+use axum::response::Response;
+use tracing::error;
+
+use crate::{
+    traits::ApiKeyManager,
+    types::{ApiKey, Lease},
+};
+
+/// Response extension through which a handler reports the actual cost of a request.
+///
+/// `ApiKeyMiddleware` charges a single unit before dispatching the request and settles the
+/// difference once the response is available, e.g. `(Extension(UsageCost(rows)), Json(body))`.
+/// Responses with a server error status are never charged, whatever cost they report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UsageCost(pub u32);
+
+const PRE_CHARGED_UNITS: u32 = 1;
+
+/// Returns the number of units the response should cost, or `None` to keep the pre-charged unit.
+pub(crate) fn response_cost(response: &Response) -> Option<u32> {
+    match response.status().is_server_error() {
+        true => Some(0),
+        false => response.extensions().get::<UsageCost>().map(|UsageCost(cost)| *cost),
+    }
+}
+
+pub(crate) async fn settle_usage(
+    manager: &(impl ApiKeyManager + Send + Sync),
+    api_key: &ApiKey,
+    lease: &Lease,
+    cost: u32,
+) {
+    let result = match cost.cmp(&PRE_CHARGED_UNITS) {
+        Ordering::Greater => manager.charge_key(api_key, lease, cost - PRE_CHARGED_UNITS).await,
+        Ordering::Less => manager.refund_key(api_key, lease, PRE_CHARGED_UNITS - cost).await,
+        Ordering::Equal => Ok(()),
+    };
+
+    if let Err(e) = result {
+        error!("Unable to settle usage for the request: {e:?}");
+    }
+}
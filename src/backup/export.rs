@@ -0,0 +1,99 @@
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+use super::{DumpHeader, ExportCheckpoint};
+use crate::{
+    errors::{ApiKeyStorageError, BackupError},
+    traits::ApiKeyLister,
+};
+
+const DEFAULT_BATCH_SIZE: usize = 500;
+
+/// Writes the keys of a storage to a dump, one page of keys at a time.
+pub struct Exporter<S>
+where
+    S: ApiKeyLister + Send + Sync,
+{
+    storage: S,
+    batch_size: usize,
+    checkpoint: Option<ExportCheckpoint>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExportReport {
+    pub exported: u64,
+}
+
+impl<S> Exporter<S>
+where
+    S: ApiKeyLister + Send + Sync,
+{
+    pub fn new(storage: S) -> Self {
+        Self { storage, batch_size: DEFAULT_BATCH_SIZE, checkpoint: None }
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Continues an export from `checkpoint`, appending to the dump without writing the header
+    /// again.
+    pub fn resume_from(mut self, checkpoint: ExportCheckpoint) -> Self {
+        self.checkpoint = Some(checkpoint);
+        self
+    }
+
+    /// Writes the dump, calling `on_checkpoint` after every batch written and flushed.
+    pub async fn run<W>(
+        &self,
+        writer: &mut W,
+        mut on_checkpoint: impl FnMut(&ExportCheckpoint) + Send,
+    ) -> Result<ExportReport, BackupError>
+    where
+        W: AsyncWrite + Unpin + Send,
+    {
+        let mut report = ExportReport::default();
+
+        let mut checkpoint = match &self.checkpoint {
+            Some(checkpoint) => checkpoint.clone(),
+            None => {
+                let header = format!("{}\n", to_json(&DumpHeader::new())?);
+                writer.write_all(header.as_bytes()).await?;
+
+                ExportCheckpoint { cursor: None, exported: 0, bytes: header.len() as u64 }
+            }
+        };
+
+        loop {
+            let page = self.storage.list_api_keys(checkpoint.cursor.as_deref(), self.batch_size).await?;
+
+            let mut lines = String::new();
+            for api_key in &page.api_keys {
+                lines.push_str(&to_json(api_key)?);
+                lines.push('\n');
+            }
+
+            writer.write_all(lines.as_bytes()).await?;
+            writer.flush().await?;
+
+            report.exported += page.api_keys.len() as u64;
+            checkpoint.exported += page.api_keys.len() as u64;
+            checkpoint.bytes += lines.len() as u64;
+
+            match page.next_cursor {
+                Some(cursor) => {
+                    checkpoint.cursor = Some(cursor);
+                    on_checkpoint(&checkpoint);
+                }
+                None => break,
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+fn to_json(value: &impl serde::Serialize) -> Result<String, BackupError> {
+    serde_json::to_string(value)
+        .map_err(|e| BackupError::Storage(ApiKeyStorageError::SerializationError(e.into())))
+}
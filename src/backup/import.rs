@@ -0,0 +1,184 @@
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+
+use super::{DumpHeader, ImportCheckpoint, DUMP_FORMAT, DUMP_VERSION};
+use crate::{
+    errors::{ApiKeyStorageError, BackupError, BoxError},
+    schema,
+    traits::ApiKeyStorage,
+    types::ApiKey,
+};
+
+const DEFAULT_CHECKPOINT_INTERVAL: u64 = 500;
+
+/// What to do with a key of the dump that already exists in the storage.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Stop the import with `BackupError::Conflict`.
+    #[default]
+    Fail,
+    /// Keep the stored key.
+    Skip,
+    /// Replace the stored key. The key is deleted then stored again, which is not atomic.
+    Overwrite,
+}
+
+/// Stores the keys of a dump in a storage.
+pub struct Importer<S>
+where
+    S: ApiKeyStorage + Send + Sync,
+{
+    storage: S,
+    conflict_policy: ConflictPolicy,
+    dry_run: bool,
+    checkpoint: Option<ImportCheckpoint>,
+    checkpoint_interval: u64,
+}
+
+/// Keys imported, or that would have been with a dry run.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportReport {
+    pub imported: u64,
+    pub overwritten: u64,
+    pub skipped: u64,
+}
+
+impl<S> Importer<S>
+where
+    S: ApiKeyStorage + Send + Sync,
+{
+    pub fn new(storage: S) -> Self {
+        Self {
+            storage,
+            conflict_policy: ConflictPolicy::default(),
+            dry_run: false,
+            checkpoint: None,
+            checkpoint_interval: DEFAULT_CHECKPOINT_INTERVAL,
+        }
+    }
+
+    pub fn with_conflict_policy(mut self, conflict_policy: ConflictPolicy) -> Self {
+        self.conflict_policy = conflict_policy;
+        self
+    }
+
+    /// Reads the whole dump and reports what would be imported, without writing to the storage.
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub fn with_checkpoint_interval(mut self, records: u64) -> Self {
+        self.checkpoint_interval = records.max(1);
+        self
+    }
+
+    /// Skips the keys of the dump processed before `checkpoint` was taken.
+    pub fn resume_from(mut self, checkpoint: ImportCheckpoint) -> Self {
+        self.checkpoint = Some(checkpoint);
+        self
+    }
+
+    /// Imports the dump, calling `on_checkpoint` every `checkpoint_interval` keys (never on a dry
+    /// run).
+    pub async fn run<R>(
+        &mut self,
+        reader: R,
+        mut on_checkpoint: impl FnMut(&ImportCheckpoint) + Send,
+    ) -> Result<ImportReport, BackupError>
+    where
+        R: AsyncBufRead + Unpin + Send,
+    {
+        let mut lines = reader.lines();
+        let mut line = 1;
+
+        let header = lines.next_line().await?.ok_or_else(|| invalid(line, "the dump is empty"))?;
+        let header: DumpHeader = serde_json::from_str(&header).map_err(|e| invalid(line, e))?;
+
+        if header.format != DUMP_FORMAT {
+            return Err(invalid(line, format!("unknown format {}", header.format)));
+        }
+
+        if header.version > DUMP_VERSION {
+            return Err(BackupError::UnsupportedVersion(header.version));
+        }
+
+        let mut report = ImportReport::default();
+        let mut checkpoint = self.checkpoint.clone().unwrap_or_default();
+        let mut records = 0;
+
+        while let Some(record) = lines.next_line().await? {
+            line += 1;
+
+            if record.trim().is_empty() {
+                continue;
+            }
+
+            records += 1;
+            if records <= checkpoint.records {
+                continue;
+            }
+
+            // Keys exported by older versions are upgraded, see `schema`.
+            let api_key = serde_json::from_str(&record)
+                .map_err(BoxError::from)
+                .and_then(|record| schema::upcast(record).map_err(BoxError::from))
+                .map_err(|e| invalid(line, e))?
+                .api_key;
+
+            match self.import(&api_key).await? {
+                Imported::New => report.imported += 1,
+                Imported::Conflict => match self.conflict_policy {
+                    ConflictPolicy::Fail => return Err(BackupError::Conflict { line, key_hash: api_key.key_hash() }),
+                    ConflictPolicy::Skip => report.skipped += 1,
+                    ConflictPolicy::Overwrite => {
+                        self.overwrite(&api_key).await?;
+                        report.overwritten += 1;
+                    }
+                },
+            }
+
+            checkpoint.records = records;
+            if !self.dry_run && records % self.checkpoint_interval == 0 {
+                on_checkpoint(&checkpoint);
+            }
+        }
+
+        Ok(report)
+    }
+
+    async fn import(&mut self, api_key: &ApiKey) -> Result<Imported, ApiKeyStorageError> {
+        if self.dry_run {
+            return match self.storage.retrieve_api_key(&api_key.key).await {
+                Ok(_) => Ok(Imported::Conflict),
+                Err(ApiKeyStorageError::KeyNotFound) => Ok(Imported::New),
+                Err(e) => Err(e),
+            };
+        }
+
+        match self.storage.store_api_key(&api_key.key, api_key).await {
+            Ok(_) => Ok(Imported::New),
+            Err(ApiKeyStorageError::KeyAlreadyExists) => Ok(Imported::Conflict),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn overwrite(&mut self, api_key: &ApiKey) -> Result<(), ApiKeyStorageError> {
+        if self.dry_run {
+            return Ok(());
+        }
+
+        self.storage.delete_api_key(&api_key.key).await?;
+        self.storage.store_api_key(&api_key.key, api_key).await?;
+
+        Ok(())
+    }
+}
+
+enum Imported {
+    New,
+    Conflict,
+}
+
+fn invalid(line: usize, source: impl Into<BoxError>) -> BackupError {
+    BackupError::InvalidDump { line, source: source.into() }
+}
@@ -0,0 +1,48 @@
+//! Export and import of the keys of a storage as a JSON Lines dump, to back them up or to move
+//! them from one storage to another.
+//!
+//! The first line of a dump is a `DumpHeader`, every following line is an `ApiKey`.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+pub mod export;
+pub mod import;
+
+pub const DUMP_FORMAT: &str = "apikeys-dump";
+/// Version written by `Exporter`. `Importer` reads this version and the older ones.
+pub const DUMP_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DumpHeader {
+    pub format: String,
+    pub version: u32,
+    pub exported_at: DateTime<Utc>,
+}
+
+impl DumpHeader {
+    pub fn new() -> Self {
+        Self { format: DUMP_FORMAT.to_string(), version: DUMP_VERSION, exported_at: Utc::now() }
+    }
+}
+
+impl Default for DumpHeader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Position of an interrupted export. Truncate the dump to `bytes` before resuming, so that keys
+/// written after the checkpoint are not exported twice.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportCheckpoint {
+    pub cursor: Option<String>,
+    pub exported: u64,
+    pub bytes: u64,
+}
+
+/// Position of an interrupted import: the number of keys of the dump already processed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportCheckpoint {
+    pub records: u64,
+}
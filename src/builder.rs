@@ -0,0 +1,223 @@
+use std::sync::Arc;
+
+use rand::{distr::Alphanumeric, Rng};
+
+use crate::{
+    clock::{system_clock, Clock},
+    errors::ApiKeyValidationError,
+    schema::CURRENT_SCHEMA_VERSION,
+    types::{ApiKey, ApiKeyLimit, ApiKeyLimits, ApiKeyRestrictions, ApiKeyStatus},
+};
+
+/// Length of the random part of generated keys. 40 alphanumeric characters carry about 238 bits.
+const GENERATED_KEY_LENGTH: usize = 40;
+/// Shortest key accepted by `ApiKeyBuilder::with_key`.
+pub const MIN_KEY_LENGTH: usize = 16;
+
+/// Limits of the usual tiers of an API. Every limit can still be changed after applying a plan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ApiKeyPlan {
+    /// 60 reads and 10 writes per minute, 2 concurrent requests.
+    #[default]
+    Free,
+    /// 600 reads and 120 writes per minute, 10 concurrent requests.
+    Pro,
+    /// No limit at all, e.g. for internal services.
+    Unlimited,
+}
+
+impl ApiKeyPlan {
+    pub fn limits(&self) -> ApiKeyLimits {
+        let (reads, writes, concurrent) = match self {
+            ApiKeyPlan::Free => (60, 10, 2),
+            ApiKeyPlan::Pro => (600, 120, 10),
+            ApiKeyPlan::Unlimited => {
+                return ApiKeyLimits {
+                    max_reads_per_minute: ApiKeyLimit::Unlimited,
+                    max_writes_per_minute: ApiKeyLimit::Unlimited,
+                    max_concurrent_requests: ApiKeyLimit::Unlimited,
+                }
+            }
+        };
+
+        ApiKeyLimits {
+            max_reads_per_minute: ApiKeyLimit::Limited(reads),
+            max_writes_per_minute: ApiKeyLimit::Limited(writes),
+            max_concurrent_requests: ApiKeyLimit::Limited(concurrent),
+        }
+    }
+}
+
+/// Builds a new, active `ApiKey` with the limits of `ApiKeyPlan::Free` and a generated key, see
+/// `ApiKey::builder`. Everything is validated by `build`.
+#[derive(Clone)]
+pub struct ApiKeyBuilder {
+    key: Option<String>,
+    key_prefix: Option<String>,
+    limits: ApiKeyLimits,
+    allowed_domains: Vec<String>,
+    status: ApiKeyStatus,
+    owner: Option<String>,
+    clock: Arc<dyn Clock>,
+}
+
+impl Default for ApiKeyBuilder {
+    fn default() -> Self {
+        Self {
+            key: None,
+            key_prefix: None,
+            limits: ApiKeyPlan::default().limits(),
+            allowed_domains: Vec::new(),
+            status: ApiKeyStatus::Active,
+            owner: None,
+            clock: system_clock(),
+        }
+    }
+}
+
+impl ApiKeyBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Uses `key` instead of generating one, e.g. to import keys issued by another system.
+    pub fn with_key(mut self, key: impl Into<String>) -> Self {
+        self.key = Some(key.into());
+        self
+    }
+
+    /// Prepends `{prefix}_` to the generated key, so that keys can be recognized (`live`, `test`...).
+    /// Prefixes are made of ASCII letters, digits and `-`.
+    pub fn with_key_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.key_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Replaces every limit with those of `plan`.
+    pub fn with_plan(mut self, plan: ApiKeyPlan) -> Self {
+        self.limits = plan.limits();
+        self
+    }
+
+    pub fn with_reads_per_minute(mut self, limit: ApiKeyLimit) -> Self {
+        self.limits.max_reads_per_minute = limit;
+        self
+    }
+
+    pub fn with_writes_per_minute(mut self, limit: ApiKeyLimit) -> Self {
+        self.limits.max_writes_per_minute = limit;
+        self
+    }
+
+    pub fn with_concurrent_requests(mut self, limit: ApiKeyLimit) -> Self {
+        self.limits.max_concurrent_requests = limit;
+        self
+    }
+
+    /// Adds a host name, such as `example.com`, to the domains the key may be used from.
+    pub fn with_allowed_domain(mut self, domain: impl Into<String>) -> Self {
+        self.allowed_domains.push(domain.into());
+        self
+    }
+
+    pub fn with_status(mut self, status: ApiKeyStatus) -> Self {
+        self.status = status;
+        self
+    }
+
+    pub fn with_owner(mut self, owner: impl Into<String>) -> Self {
+        self.owner = Some(owner.into());
+        self
+    }
+
+    /// Clock `created_at` and `updated_at` are read from.
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    pub fn build(self) -> Result<ApiKey, ApiKeyValidationError> {
+        let key = match (self.key, self.key_prefix) {
+            (Some(_), Some(_)) => return Err(ApiKeyValidationError::PrefixWithExplicitKey),
+            (Some(key), None) => validate_key(key)?,
+            (None, prefix) => generate_key(prefix.as_deref())?,
+        };
+
+        validate_limit("max_reads_per_minute", &self.limits.max_reads_per_minute)?;
+        validate_limit("max_writes_per_minute", &self.limits.max_writes_per_minute)?;
+        validate_limit("max_concurrent_requests", &self.limits.max_concurrent_requests)?;
+
+        for domain in &self.allowed_domains {
+            validate_domain(domain)?;
+        }
+
+        if self.owner.as_deref().is_some_and(|owner| owner.trim().is_empty()) {
+            return Err(ApiKeyValidationError::EmptyOwner);
+        }
+
+        let now = self.clock.now();
+
+        Ok(ApiKey {
+            key,
+            limits: self.limits,
+            restrictions: ApiKeyRestrictions { allowed_domains: self.allowed_domains },
+            status: self.status,
+            owner: self.owner,
+            created_at: now,
+            updated_at: now,
+            schema_version: CURRENT_SCHEMA_VERSION,
+        })
+    }
+}
+
+fn generate_key(prefix: Option<&str>) -> Result<String, ApiKeyValidationError> {
+    let random: String =
+        rand::rng().sample_iter(&Alphanumeric).take(GENERATED_KEY_LENGTH).map(char::from).collect();
+
+    match prefix {
+        Some(prefix) => {
+            if prefix.is_empty() || !prefix.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+                return Err(ApiKeyValidationError::InvalidKeyPrefix(prefix.to_string()));
+            }
+
+            Ok(format!("{prefix}_{random}"))
+        }
+        None => Ok(random),
+    }
+}
+
+fn validate_key(key: String) -> Result<String, ApiKeyValidationError> {
+    // Keys travel in headers, so they are restricted to visible ASCII characters.
+    if let Some(character) = key.chars().find(|c| !c.is_ascii_graphic()) {
+        return Err(ApiKeyValidationError::InvalidKeyCharacter(character));
+    }
+
+    if key.len() < MIN_KEY_LENGTH {
+        return Err(ApiKeyValidationError::KeyTooShort { min_length: MIN_KEY_LENGTH });
+    }
+
+    Ok(key)
+}
+
+fn validate_limit(limit_name: &'static str, limit: &ApiKeyLimit) -> Result<(), ApiKeyValidationError> {
+    match limit {
+        ApiKeyLimit::Limited(0) => Err(ApiKeyValidationError::ZeroLimit(limit_name)),
+        _ => Ok(()),
+    }
+}
+
+/// Host names made of dot separated labels of letters, digits and inner hyphens.
+fn validate_domain(domain: &str) -> Result<(), ApiKeyValidationError> {
+    let valid_label = |label: &str| {
+        !label.is_empty()
+            && label.len() <= 63
+            && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+    };
+
+    match domain.len() <= 253 && domain.split('.').all(valid_label) {
+        true => Ok(()),
+        false => Err(ApiKeyValidationError::InvalidDomain(domain.to_string())),
+    }
+}
@@ -0,0 +1,114 @@
+use std::{
+    sync::{Arc, Mutex, MutexGuard, OnceLock},
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+
+/// Source of the current time for everything that depends on it, such as rate limit windows.
+/// Tests use a `ManualClock` to move time forward instead of waiting.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+
+    /// Time elapsed since an arbitrary starting point. Unlike `now` it never goes backwards when the
+    /// system time is adjusted, so it is what durations such as cache TTLs are measured with.
+    fn monotonic(&self) -> Duration;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    fn monotonic(&self) -> Duration {
+        static STARTED_AT: OnceLock<Instant> = OnceLock::new();
+
+        STARTED_AT.get_or_init(Instant::now).elapsed()
+    }
+}
+
+#[derive(Debug)]
+struct ManualTime {
+    now: DateTime<Utc>,
+    monotonic: Duration,
+}
+
+/// A clock that only moves when told to. Clones share the same time.
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    time: Arc<Mutex<ManualTime>>,
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new(Utc::now())
+    }
+}
+
+impl ManualClock {
+    pub fn new(now: DateTime<Utc>) -> Self {
+        Self { time: Arc::new(Mutex::new(ManualTime { now, monotonic: Duration::ZERO })) }
+    }
+
+    /// Sets the time of day. Like an adjustment of the system time, setting it back leaves
+    /// `monotonic` where it is.
+    pub fn set(&self, now: DateTime<Utc>) {
+        let mut time = self.lock();
+        let forward = (now - time.now).to_std().unwrap_or_default();
+        time.monotonic = time.monotonic.saturating_add(forward);
+        time.now = now;
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        let mut time = self.lock();
+        time.now = add(time.now, duration);
+        time.monotonic = time.monotonic.saturating_add(duration);
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ManualTime> {
+        match self.time.lock() {
+            Ok(time) => time,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.lock().now
+    }
+
+    fn monotonic(&self) -> Duration {
+        self.lock().monotonic
+    }
+}
+
+/// Lets components share a clock, e.g. one `Arc<dyn Clock>` handed to several builders.
+impl<C> Clock for Arc<C>
+where
+    C: Clock + ?Sized,
+{
+    fn now(&self) -> DateTime<Utc> {
+        self.as_ref().now()
+    }
+
+    fn monotonic(&self) -> Duration {
+        self.as_ref().monotonic()
+    }
+}
+
+pub(crate) fn system_clock() -> Arc<dyn Clock> {
+    Arc::new(SystemClock)
+}
+
+/// `at + duration`, saturating instead of overflowing.
+pub(crate) fn add(at: DateTime<Utc>, duration: Duration) -> DateTime<Utc> {
+    chrono::Duration::from_std(duration)
+        .ok()
+        .and_then(|duration| at.checked_add_signed(duration))
+        .unwrap_or(DateTime::<Utc>::MAX_UTC)
+}
+
@@ -0,0 +1,225 @@
+use std::{future::Future, time::Duration};
+
+use chrono::{DurationRound, Utc};
+use futures_util::future::join_all;
+
+use super::{conformance_api_key, unique_key};
+use crate::{
+    clock::{Clock, ManualClock},
+    errors::ApiKeyLimiterError,
+    traits::ApiKeyLimiter,
+    types::{ApiKey, ApiKeyLimit, Lease},
+};
+
+const WINDOW: Duration = Duration::from_secs(60);
+const CONCURRENT_USERS: u32 = 100;
+
+/// Checks that a rate limiter enforces `max_reads_per_minute` like the bundled ones:
+///
+/// - a key is accepted `limit` times per window, then rejected with `RateLimitExceeded`,
+/// - usage goes back to zero once the one minute window is over,
+/// - a refund gives back at most what its lease was charged in the current window, and refunds made
+///   once that window has ended do not raise the limit of the next window,
+/// - unlimited keys are always accepted and have no rate limit status,
+/// - exactly `limit` of many concurrent uses are accepted,
+/// - `rate_limit_status` reports the limit, the remaining units and when the window ends.
+///
+/// `new_limiter` is called once per check with a clock the suite moves forward, which the limiter
+/// must read the time from. Every check uses new keys.
+pub async fn check_api_key_limiter<L, F, Fut>(new_limiter: F)
+where
+    L: ApiKeyLimiter + Clone + Send + Sync + 'static,
+    F: Fn(ManualClock) -> Fut,
+    Fut: Future<Output = L>,
+{
+    check_enforcement(&new_limiter).await;
+    check_window_reset(&new_limiter).await;
+    check_late_refunds(&new_limiter).await;
+    check_unlimited(&new_limiter).await;
+    check_concurrent_uses(&new_limiter).await;
+    check_rate_limit_status(&new_limiter).await;
+}
+
+/// A clock at the start of a minute, so that windows aligned on the clock and windows starting at
+/// the first use end at the same time.
+fn minute_aligned_clock() -> ManualClock {
+    let now = Utc::now();
+    ManualClock::new(now.duration_trunc(chrono::Duration::minutes(1)).unwrap_or(now))
+}
+
+fn limited_api_key(name: &str, limit: u32) -> ApiKey {
+    let mut api_key = conformance_api_key(&unique_key(name));
+    api_key.limits.max_reads_per_minute = ApiKeyLimit::Limited(limit);
+    api_key
+}
+
+async fn use_times(limiter: &(impl ApiKeyLimiter + Sync), api_key: &ApiKey, times: u32) {
+    for attempt in 1..=times {
+        if let Err(e) = limiter.use_key(api_key).await {
+            panic!("Use {attempt} of {times} should be accepted, got {e}");
+        }
+    }
+}
+
+async fn acquire_times(limiter: &(impl ApiKeyLimiter + Sync), api_key: &ApiKey, times: u32) -> Vec<Lease> {
+    let mut leases = Vec::new();
+
+    for attempt in 1..=times {
+        let lease = Lease::new();
+        if let Err(e) = limiter.acquire_key(api_key, &lease).await {
+            panic!("Acquisition {attempt} of {times} should be accepted, got {e}");
+        }
+        leases.push(lease);
+    }
+
+    leases
+}
+
+async fn refund(limiter: &(impl ApiKeyLimiter + Sync), api_key: &ApiKey, lease: &Lease, units: u32) {
+    if let Err(e) = limiter.refund_key(api_key, lease, units).await {
+        panic!("A refund should be accepted, got {e}");
+    }
+}
+
+async fn assert_rate_limited(limiter: &(impl ApiKeyLimiter + Sync), api_key: &ApiKey, message: &str) {
+    match limiter.use_key(api_key).await {
+        Err(ApiKeyLimiterError::RateLimitExceeded) => {}
+        result => panic!("{message}, got {result:?}"),
+    }
+}
+
+async fn check_enforcement<L, F, Fut>(new_limiter: &F)
+where
+    L: ApiKeyLimiter + Send + Sync,
+    F: Fn(ManualClock) -> Fut,
+    Fut: Future<Output = L>,
+{
+    let limiter = new_limiter(minute_aligned_clock()).await;
+    let api_key = limited_api_key("enforcement", 5);
+
+    use_times(&limiter, &api_key, 5).await;
+    assert_rate_limited(&limiter, &api_key, "A key over its limit should be rejected with RateLimitExceeded").await;
+}
+
+async fn check_window_reset<L, F, Fut>(new_limiter: &F)
+where
+    L: ApiKeyLimiter + Send + Sync,
+    F: Fn(ManualClock) -> Fut,
+    Fut: Future<Output = L>,
+{
+    let clock = minute_aligned_clock();
+    let limiter = new_limiter(clock.clone()).await;
+    let api_key = limited_api_key("window-reset", 2);
+
+    use_times(&limiter, &api_key, 2).await;
+
+    clock.advance(WINDOW / 2);
+    assert_rate_limited(&limiter, &api_key, "The limit should hold for the whole window").await;
+
+    clock.advance(WINDOW / 2);
+    use_times(&limiter, &api_key, 2).await;
+}
+
+async fn check_late_refunds<L, F, Fut>(new_limiter: &F)
+where
+    L: ApiKeyLimiter + Send + Sync,
+    F: Fn(ManualClock) -> Fut,
+    Fut: Future<Output = L>,
+{
+    let clock = minute_aligned_clock();
+    let limiter = new_limiter(clock.clone()).await;
+    let api_key = limited_api_key("late-refunds", 2);
+
+    let late_leases = acquire_times(&limiter, &api_key, 2).await;
+
+    clock.advance(WINDOW);
+    let current_leases = acquire_times(&limiter, &api_key, 1).await;
+
+    for lease in &late_leases {
+        refund(&limiter, &api_key, lease, 2).await;
+    }
+
+    use_times(&limiter, &api_key, 1).await;
+
+    // Only gives back the unit its lease was charged, leaving room for one more use.
+    refund(&limiter, &api_key, &current_leases[0], 2).await;
+
+    use_times(&limiter, &api_key, 1).await;
+    assert_rate_limited(&limiter, &api_key, "Refunds should give back no more than their lease was charged").await;
+}
+
+async fn check_unlimited<L, F, Fut>(new_limiter: &F)
+where
+    L: ApiKeyLimiter + Send + Sync,
+    F: Fn(ManualClock) -> Fut,
+    Fut: Future<Output = L>,
+{
+    let limiter = new_limiter(minute_aligned_clock()).await;
+
+    let mut api_key = conformance_api_key(&unique_key("unlimited"));
+    api_key.limits.max_reads_per_minute = ApiKeyLimit::Unlimited;
+
+    use_times(&limiter, &api_key, 200).await;
+
+    match limiter.rate_limit_status(&api_key).await {
+        Ok(None) => {}
+        result => panic!("An unlimited key should have no rate limit status, got {result:?}"),
+    }
+}
+
+async fn check_concurrent_uses<L, F, Fut>(new_limiter: &F)
+where
+    L: ApiKeyLimiter + Clone + Send + Sync + 'static,
+    F: Fn(ManualClock) -> Fut,
+    Fut: Future<Output = L>,
+{
+    let limiter = new_limiter(minute_aligned_clock()).await;
+    let api_key = limited_api_key("concurrency", CONCURRENT_USERS / 2);
+
+    let users = (0..CONCURRENT_USERS).map(|_| {
+        let (limiter, api_key) = (limiter.clone(), api_key.clone());
+        tokio::spawn(async move { limiter.use_key(&api_key).await })
+    });
+
+    let mut accepted = 0;
+    for result in join_all(users).await {
+        match result.expect("The user should not panic") {
+            Ok(()) => accepted += 1,
+            Err(ApiKeyLimiterError::RateLimitExceeded) => {}
+            Err(e) => panic!("Concurrent uses should only fail with RateLimitExceeded, got {e}"),
+        }
+    }
+
+    assert_eq!(accepted, CONCURRENT_USERS / 2, "Exactly the limit of concurrent uses should be accepted");
+}
+
+async fn check_rate_limit_status<L, F, Fut>(new_limiter: &F)
+where
+    L: ApiKeyLimiter + Send + Sync,
+    F: Fn(ManualClock) -> Fut,
+    Fut: Future<Output = L>,
+{
+    let clock = minute_aligned_clock();
+    let limiter = new_limiter(clock.clone()).await;
+    let api_key = limited_api_key("status", 5);
+
+    use_times(&limiter, &api_key, 2).await;
+
+    let status = match limiter.rate_limit_status(&api_key).await {
+        Ok(Some(status)) => status,
+        result => panic!("A limited key should have a rate limit status, got {result:?}"),
+    };
+
+    assert_eq!(status.limit, 5);
+    assert_eq!(status.remaining, 3, "Every use should count against the limit");
+    assert!(
+        status.reset_at > clock.now() && status.reset_at <= clock.now() + WINDOW,
+        "The window should end within a minute, got {}",
+        status.reset_at
+    );
+
+    use_times(&limiter, &api_key, 3).await;
+
+    let status = limiter.rate_limit_status(&api_key).await.expect("The status should be read");
+    assert_eq!(status.map(|status| status.remaining), Some(0), "An exhausted key should have nothing remaining");
+}
@@ -0,0 +1,43 @@
+//! Test suites checking that an implementation of the traits of this crate behaves like the
+//! bundled ones. They panic on the first deviation, so they are meant to be called from tests.
+
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    schema::CURRENT_SCHEMA_VERSION,
+    types::{ApiKey, ApiKeyLimit, ApiKeyLimits, ApiKeyRestrictions, ApiKeyStatus},
+};
+
+pub mod limiter;
+pub mod storage;
+
+/// Key unique to this run, so that suites can share a database with other data or runs.
+fn unique_key(name: &str) -> String {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_nanos()).unwrap_or_default();
+
+    format!("conformance-{name}-{}-{nanos}-{}", std::process::id(), COUNTER.fetch_add(1, Ordering::SeqCst))
+}
+
+fn conformance_api_key(key: &str) -> ApiKey {
+    let now = chrono::Utc::now();
+
+    ApiKey {
+        key: key.to_string(),
+        limits: ApiKeyLimits {
+            max_reads_per_minute: ApiKeyLimit::Limited(100),
+            max_writes_per_minute: ApiKeyLimit::Unlimited,
+            max_concurrent_requests: ApiKeyLimit::Limited(10),
+        },
+        restrictions: ApiKeyRestrictions { allowed_domains: vec!["example.com".to_string()] },
+        status: ApiKeyStatus::Active,
+        owner: Some("conformance".to_string()),
+        created_at: now,
+        updated_at: now,
+        schema_version: CURRENT_SCHEMA_VERSION,
+    }
+}
@@ -0,0 +1,137 @@
+use futures_util::future::join_all;
+
+use super::{conformance_api_key, unique_key};
+use crate::{
+    errors::ApiKeyStorageError,
+    traits::ApiKeyStorage,
+    types::{ApiKeyLimit, ApiKeyStatus},
+};
+
+const CONCURRENT_WRITERS: usize = 16;
+
+/// Checks the semantics every `ApiKeyStorage` is expected to follow:
+///
+/// - unknown keys are reported as `KeyNotFound`,
+/// - stored keys are retrieved as they were stored,
+/// - storing a key twice fails with `KeyAlreadyExists` and keeps the first record,
+/// - deleting returns whether the key existed, and deleted keys are not found anymore,
+/// - clones share their keys, and only one of many concurrent stores of the same key succeeds.
+///
+/// The suite only touches keys it creates, with unique names, and deletes them when it succeeds.
+/// Read-only storages such as `FileStorage` cannot be checked.
+pub async fn check_api_key_storage<S>(storage: S)
+where
+    S: ApiKeyStorage + Clone + Send + Sync + 'static,
+{
+    check_not_found(&storage).await;
+    check_round_trip(storage.clone()).await;
+    check_duplicates(storage.clone()).await;
+    check_delete(storage.clone()).await;
+    check_concurrent_stores(storage).await;
+}
+
+async fn check_not_found<S>(storage: &S)
+where
+    S: ApiKeyStorage + Send + Sync,
+{
+    match storage.retrieve_api_key(&unique_key("missing")).await {
+        Err(ApiKeyStorageError::KeyNotFound) => {}
+        result => panic!("Retrieving an unknown key should fail with KeyNotFound, got {result:?}"),
+    }
+}
+
+async fn check_round_trip<S>(mut storage: S)
+where
+    S: ApiKeyStorage + Send + Sync,
+{
+    let key = unique_key("round-trip");
+    let api_key = conformance_api_key(&key);
+
+    match storage.store_api_key(&key, &api_key).await {
+        Ok(stored) => assert_eq!(stored, key, "Storing a key should return the key"),
+        Err(e) => panic!("Storing a new key should succeed, got {e}"),
+    }
+
+    let retrieved = storage.retrieve_api_key(&key).await.expect("A stored key should be retrieved");
+
+    assert_eq!(retrieved.key, api_key.key);
+    assert_eq!(retrieved.owner, api_key.owner);
+    assert_eq!(retrieved.restrictions.allowed_domains, api_key.restrictions.allowed_domains);
+    assert!(matches!(retrieved.status, ApiKeyStatus::Active), "The status should be kept");
+    assert!(matches!(retrieved.limits.max_reads_per_minute, ApiKeyLimit::Limited(100)), "Limits should be kept");
+    assert!(matches!(retrieved.limits.max_writes_per_minute, ApiKeyLimit::Unlimited), "Limits should be kept");
+    assert!(matches!(retrieved.limits.max_concurrent_requests, ApiKeyLimit::Limited(10)), "Limits should be kept");
+    // Backends may store timestamps with less precision.
+    assert_eq!(retrieved.created_at.timestamp(), api_key.created_at.timestamp());
+
+    let _ = storage.delete_api_key(&key).await;
+}
+
+async fn check_duplicates<S>(mut storage: S)
+where
+    S: ApiKeyStorage + Send + Sync,
+{
+    let key = unique_key("duplicate");
+    let api_key = conformance_api_key(&key);
+
+    storage.store_api_key(&key, &api_key).await.expect("Storing a new key should succeed");
+
+    let mut duplicate = api_key.clone();
+    duplicate.owner = Some("someone-else".to_string());
+
+    match storage.store_api_key(&key, &duplicate).await {
+        Err(ApiKeyStorageError::KeyAlreadyExists) => {}
+        result => panic!("Storing a key twice should fail with KeyAlreadyExists, got {result:?}"),
+    }
+
+    let retrieved = storage.retrieve_api_key(&key).await.expect("The first record should be kept");
+    assert_eq!(retrieved.owner, api_key.owner, "A rejected duplicate should not replace the first record");
+
+    let _ = storage.delete_api_key(&key).await;
+}
+
+async fn check_delete<S>(mut storage: S)
+where
+    S: ApiKeyStorage + Send + Sync,
+{
+    let key = unique_key("delete");
+
+    storage.store_api_key(&key, &conformance_api_key(&key)).await.expect("Storing a new key should succeed");
+
+    assert!(storage.delete_api_key(&key).await.expect("Deleting a key should succeed"), "The key existed");
+
+    match storage.retrieve_api_key(&key).await {
+        Err(ApiKeyStorageError::KeyNotFound) => {}
+        result => panic!("A deleted key should not be found, got {result:?}"),
+    }
+
+    assert!(!storage.delete_api_key(&key).await.expect("Deleting a missing key should succeed"), "The key was gone");
+}
+
+async fn check_concurrent_stores<S>(storage: S)
+where
+    S: ApiKeyStorage + Clone + Send + Sync + 'static,
+{
+    let key = unique_key("concurrent");
+    let api_key = conformance_api_key(&key);
+
+    let writers = (0..CONCURRENT_WRITERS).map(|_| {
+        let (mut storage, key, api_key) = (storage.clone(), key.clone(), api_key.clone());
+        tokio::spawn(async move { storage.store_api_key(&key, &api_key).await })
+    });
+
+    let mut stored = 0;
+    for result in join_all(writers).await {
+        match result.expect("The writer should not panic") {
+            Ok(_) => stored += 1,
+            Err(ApiKeyStorageError::KeyAlreadyExists) => {}
+            Err(e) => panic!("Concurrent stores should only fail with KeyAlreadyExists, got {e}"),
+        }
+    }
+
+    assert_eq!(stored, 1, "Exactly one of the concurrent stores of a key should succeed");
+    assert!(storage.retrieve_api_key(&key).await.is_ok(), "A key stored by a clone should be visible to the others");
+
+    let mut storage = storage;
+    let _ = storage.delete_api_key(&key).await;
+}
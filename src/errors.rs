@@ -1,11 +1,36 @@
-use std::fmt;
+use std::{error::Error, fmt, path::PathBuf};
+
+/// Error of a backend (database driver, serializer...) carried as the source of the errors of this crate.
+pub type BoxError = Box<dyn Error + Send + Sync + 'static>;
+
+/// Shows an error followed by its sources. The errors of this crate leave the error they wrap out of
+/// their message, since it is their `source()`.
+pub(crate) struct ErrorChain<'a>(pub(crate) &'a (dyn Error + 'static));
+
+impl fmt::Display for ErrorChain<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)?;
+
+        let mut source = self.0.source();
+
+        while let Some(error) = source {
+            write!(f, ": {}", error)?;
+            source = error.source();
+        }
+
+        Ok(())
+    }
+}
 
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum ApiKeyStorageError {
     KeyNotFound,
     KeyAlreadyExists,
-    SerializationError(String),
-    StorageError(String),
+    ReadOnly,
+    KeyMismatch,
+    SerializationError(BoxError),
+    StorageError(BoxError),
 }
 
 impl fmt::Display for ApiKeyStorageError {
@@ -13,8 +38,19 @@ impl fmt::Display for ApiKeyStorageError {
         match self {
             ApiKeyStorageError::KeyNotFound => write!(f, "Key not found"),
             ApiKeyStorageError::KeyAlreadyExists => write!(f, "Key already exists"),
-            ApiKeyStorageError::SerializationError(e) => write!(f, "Serialization error: {}", e),
-            ApiKeyStorageError::StorageError(e) => write!(f, "Storage error: {}", e),
+            ApiKeyStorageError::ReadOnly => write!(f, "Storage is read-only"),
+            ApiKeyStorageError::KeyMismatch => write!(f, "Key does not match the key of the record"),
+            ApiKeyStorageError::SerializationError(_) => write!(f, "Serialization error"),
+            ApiKeyStorageError::StorageError(_) => write!(f, "Storage error"),
+        }
+    }
+}
+
+impl Error for ApiKeyStorageError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ApiKeyStorageError::SerializationError(e) | ApiKeyStorageError::StorageError(e) => Some(e.as_ref()),
+            _ => None,
         }
     }
 }
@@ -24,6 +60,8 @@ impl ApiKeyStorageError {
         match self {
             ApiKeyStorageError::KeyNotFound => "KeyNotFound".to_string(),
             ApiKeyStorageError::KeyAlreadyExists => "KeyAlreadyExists".to_string(),
+            ApiKeyStorageError::ReadOnly => "ReadOnly".to_string(),
+            ApiKeyStorageError::KeyMismatch => "KeyMismatch".to_string(),
             ApiKeyStorageError::SerializationError(_) => "SerializationError".to_string(),
             ApiKeyStorageError::StorageError(_) => "StorageError".to_string(),
         }
@@ -31,23 +69,57 @@ impl ApiKeyStorageError {
 }
 
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum ApiKeyManagerError {
     StorageError(ApiKeyStorageError),
     LimiterError(ApiKeyLimiterError),
-    Other(String),
+    Other(BoxError),
+}
+
+// Storage and limiter errors are shown as they are, they already say where they come from.
+impl fmt::Display for ApiKeyManagerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ApiKeyManagerError::StorageError(e) => write!(f, "{}", e),
+            ApiKeyManagerError::LimiterError(e) => write!(f, "{}", e),
+            ApiKeyManagerError::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for ApiKeyManagerError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ApiKeyManagerError::StorageError(e) => e.source(),
+            ApiKeyManagerError::LimiterError(e) => e.source(),
+            ApiKeyManagerError::Other(e) => e.source(),
+        }
+    }
 }
 
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum ApiKeyLimiterError {
     RateLimitExceeded,
-    Other(String),
+    ConcurrencyLimitExceeded,
+    Other(BoxError),
 }
 
 impl fmt::Display for ApiKeyLimiterError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             ApiKeyLimiterError::RateLimitExceeded => write!(f, "Rate limit exceeded"),
-            ApiKeyLimiterError::Other(e) => write!(f, "Other error: {}", e),
+            ApiKeyLimiterError::ConcurrencyLimitExceeded => write!(f, "Concurrent request limit exceeded"),
+            ApiKeyLimiterError::Other(_) => write!(f, "Limiter error"),
+        }
+    }
+}
+
+impl Error for ApiKeyLimiterError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ApiKeyLimiterError::Other(e) => Some(e.as_ref()),
+            _ => None,
         }
     }
 }
@@ -56,7 +128,125 @@ impl ApiKeyLimiterError {
     pub fn to_message_type(&self) -> String {
         match self {
             ApiKeyLimiterError::RateLimitExceeded => "RateLimitExceeded".to_string(),
+            ApiKeyLimiterError::ConcurrencyLimitExceeded => "ConcurrencyLimitExceeded".to_string(),
             ApiKeyLimiterError::Other(_) => "ApiLimiter::Other".to_string(),
         }
     }
 }
+
+/// Reasons `ApiKeyBuilder::build` rejects a key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ApiKeyValidationError {
+    KeyTooShort { min_length: usize },
+    InvalidKeyCharacter(char),
+    InvalidKeyPrefix(String),
+    /// A prefix only applies to generated keys.
+    PrefixWithExplicitKey,
+    /// Name of the limit set to zero, which would reject every request. Use a status to disable a key.
+    ZeroLimit(&'static str),
+    InvalidDomain(String),
+    EmptyOwner,
+}
+
+impl fmt::Display for ApiKeyValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ApiKeyValidationError::KeyTooShort { min_length } => {
+                write!(f, "The key must be at least {} characters long", min_length)
+            }
+            ApiKeyValidationError::InvalidKeyCharacter(c) => write!(f, "The key contains an invalid character {:?}", c),
+            ApiKeyValidationError::InvalidKeyPrefix(prefix) => write!(f, "Invalid key prefix {:?}", prefix),
+            ApiKeyValidationError::PrefixWithExplicitKey => {
+                write!(f, "A key prefix cannot be used with an explicit key")
+            }
+            ApiKeyValidationError::ZeroLimit(limit) => write!(f, "{} must not be zero", limit),
+            ApiKeyValidationError::InvalidDomain(domain) => write!(f, "Invalid domain {:?}", domain),
+            ApiKeyValidationError::EmptyOwner => write!(f, "The owner must not be empty"),
+        }
+    }
+}
+
+impl Error for ApiKeyValidationError {}
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum FileStorageError {
+    UnsupportedFormat(PathBuf),
+    Io(PathBuf, std::io::Error),
+    Parse(PathBuf, BoxError),
+    InvalidKey { path: PathBuf, index: usize, message: String },
+}
+
+impl fmt::Display for FileStorageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FileStorageError::UnsupportedFormat(path) => {
+                write!(f, "{}: unsupported file format, expected a .yaml, .yml, .toml or .json file", path.display())
+            }
+            FileStorageError::Io(path, _) => write!(f, "{}: unable to read the file", path.display()),
+            FileStorageError::Parse(path, _) => write!(f, "{}: unable to parse the file", path.display()),
+            FileStorageError::InvalidKey { path, index, message } => {
+                write!(f, "{}: key #{} is invalid: {}", path.display(), index + 1, message)
+            }
+        }
+    }
+}
+
+impl Error for FileStorageError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            FileStorageError::Io(_, e) => Some(e),
+            FileStorageError::Parse(_, e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum BackupError {
+    Io(std::io::Error),
+    Storage(ApiKeyStorageError),
+    /// `line` is 1-based, the header being line 1.
+    InvalidDump { line: usize, source: BoxError },
+    UnsupportedVersion(u32),
+    Conflict { line: usize, key_hash: String },
+}
+
+impl fmt::Display for BackupError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BackupError::Io(_) => write!(f, "I/O error"),
+            BackupError::Storage(e) => write!(f, "{}", e),
+            BackupError::InvalidDump { line, .. } => write!(f, "Invalid dump at line {}", line),
+            BackupError::UnsupportedVersion(version) => write!(f, "Unsupported dump version {}", version),
+            BackupError::Conflict { line, key_hash } => {
+                write!(f, "Key {} at line {} already exists", key_hash, line)
+            }
+        }
+    }
+}
+
+impl Error for BackupError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            BackupError::Io(e) => Some(e),
+            BackupError::Storage(e) => e.source(),
+            BackupError::InvalidDump { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for BackupError {
+    fn from(error: std::io::Error) -> Self {
+        BackupError::Io(error)
+    }
+}
+
+impl From<ApiKeyStorageError> for BackupError {
+    fn from(error: ApiKeyStorageError) -> Self {
+        BackupError::Storage(error)
+    }
+}
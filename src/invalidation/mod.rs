@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "redis")]
+pub mod redis_invalidation;
+
+/// Bounds of the delay between two attempts of an event source to reconnect.
+#[cfg(any(feature = "mongodb", feature = "redis"))]
+pub(crate) const MIN_RECONNECT_DELAY: std::time::Duration = std::time::Duration::from_millis(100);
+#[cfg(any(feature = "mongodb", feature = "redis"))]
+pub(crate) const MAX_RECONNECT_DELAY: std::time::Duration = std::time::Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KeyChangeKind {
+    Stored,
+    Updated,
+    Deleted,
+}
+
+/// Tells caches that the key with the given hash (see `ApiKey::key_hash`) changed. Events only
+/// carry the hash so that they can travel through shared channels without leaking keys.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyChangeEvent {
+    pub key_hash: String,
+    pub kind: KeyChangeKind,
+}
+
+impl KeyChangeEvent {
+    pub fn new(key_hash: impl Into<String>, kind: KeyChangeKind) -> Self {
+        Self { key_hash: key_hash.into(), kind }
+    }
+}
+
+/// Receives key change events, typically a cache evicting the keys that changed.
+pub trait KeyChangeListener: Send + Sync + 'static {
+    fn key_changed(&self, event: &KeyChangeEvent);
+
+    /// Called when events may have been missed, e.g. after the event source reconnected. Everything
+    /// derived from the keys should be dropped.
+    fn resync(&self);
+}
@@ -0,0 +1,158 @@
+use async_trait::async_trait;
+use futures_util::StreamExt;
+use redis::{AsyncCommands, Client, RedisError};
+use tokio::task::JoinHandle;
+
+use super::{KeyChangeEvent, KeyChangeKind, KeyChangeListener, MAX_RECONNECT_DELAY, MIN_RECONNECT_DELAY};
+use crate::{
+    errors::ApiKeyStorageError, redis_connection::RedisConnection, traits::ApiKeyStorage, types::ApiKey,
+};
+
+pub const DEFAULT_INVALIDATION_CHANNEL: &str = "apikeys:invalidation";
+
+/// Wraps a storage and publishes a `KeyChangeEvent` on a Redis channel after every successful
+/// write, so that the caches of every node can be invalidated by a `RedisInvalidationSubscriber`.
+///
+/// Events are published after the write succeeded. A failure to publish is logged but does not fail
+/// the write: the caches then catch up after their TTL.
+#[derive(Clone)]
+pub struct RedisInvalidationPublisher<S>
+where
+    S: ApiKeyStorage + Send + Sync,
+{
+    inner: S,
+    connection: RedisConnection,
+    channel: String,
+}
+
+impl<S> RedisInvalidationPublisher<S>
+where
+    S: ApiKeyStorage + Send + Sync,
+{
+    pub fn new(inner: S, connection: RedisConnection) -> Self {
+        Self { inner, connection, channel: DEFAULT_INVALIDATION_CHANNEL.to_string() }
+    }
+
+    pub fn with_channel(mut self, channel: &str) -> Self {
+        self.channel = channel.to_string();
+        self
+    }
+
+    async fn publish(&self, event: KeyChangeEvent) {
+        let mut connection = self.connection.clone();
+
+        let payload = match serde_json::to_string(&event) {
+            Ok(payload) => payload,
+            Err(e) => {
+                tracing::error!("Unable to serialize key change event: {e}");
+                return;
+            }
+        };
+
+        let published: Result<i64, RedisError> = connection.publish(&self.channel, payload).await;
+
+        if let Err(e) = published {
+            tracing::error!("Unable to publish key change event for {}: {e}", event.key_hash);
+        }
+    }
+}
+
+#[async_trait]
+impl<S> ApiKeyStorage for RedisInvalidationPublisher<S>
+where
+    S: ApiKeyStorage + Send + Sync,
+{
+    async fn store_api_key(&mut self, key: &str, value: &ApiKey) -> Result<String, ApiKeyStorageError> {
+        let stored = self.inner.store_api_key(key, value).await?;
+
+        self.publish(KeyChangeEvent::new(ApiKey::hash_key(key), KeyChangeKind::Stored)).await;
+
+        Ok(stored)
+    }
+
+    async fn retrieve_api_key(&self, key: &str) -> Result<ApiKey, ApiKeyStorageError> {
+        self.inner.retrieve_api_key(key).await
+    }
+
+    async fn delete_api_key(&mut self, key: &str) -> Result<bool, ApiKeyStorageError> {
+        let deleted = self.inner.delete_api_key(key).await?;
+
+        if deleted {
+            self.publish(KeyChangeEvent::new(ApiKey::hash_key(key), KeyChangeKind::Deleted)).await;
+        }
+
+        Ok(deleted)
+    }
+}
+
+/// Listens to the key change events published by `RedisInvalidationPublisher` and forwards them to
+/// a listener, usually a `CachedStorage`.
+///
+/// Pub/sub messages sent while the subscriber is disconnected are lost, so the listener is told to
+/// `resync` every time the subscription is (re)established. With Redis Cluster, published messages
+/// reach every node and the subscriber can connect to any of them.
+pub struct RedisInvalidationSubscriber {
+    client: Client,
+    channel: String,
+}
+
+impl RedisInvalidationSubscriber {
+    pub fn new(uri: &str) -> Result<Self, RedisError> {
+        Ok(Self { client: Client::open(uri)?, channel: DEFAULT_INVALIDATION_CHANNEL.to_string() })
+    }
+
+    pub fn with_channel(mut self, channel: &str) -> Self {
+        self.channel = channel.to_string();
+        self
+    }
+
+    /// Subscribes in a background task that reconnects, with an increasing delay, until aborted.
+    pub fn spawn(self, listener: impl KeyChangeListener) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut reconnect_delay = MIN_RECONNECT_DELAY;
+
+            loop {
+                match self.listen(&listener).await {
+                    Ok(()) => {
+                        tracing::warn!("Lost the subscription to {}, reconnecting", self.channel);
+                        reconnect_delay = MIN_RECONNECT_DELAY;
+                    }
+                    Err(e) => {
+                        tracing::error!("Unable to subscribe to {}: {e}", self.channel);
+                        reconnect_delay = (reconnect_delay * 2).min(MAX_RECONNECT_DELAY);
+                    }
+                }
+
+                tokio::time::sleep(reconnect_delay).await;
+            }
+        })
+    }
+
+    /// Returns once the connection is lost.
+    async fn listen(&self, listener: &impl KeyChangeListener) -> Result<(), RedisError> {
+        let mut pubsub = self.client.get_async_pubsub().await?;
+        pubsub.subscribe(&self.channel).await?;
+
+        tracing::info!("Subscribed to key change events on {}", self.channel);
+        listener.resync();
+
+        let mut messages = pubsub.on_message();
+
+        while let Some(message) = messages.next().await {
+            let payload: String = match message.get_payload() {
+                Ok(payload) => payload,
+                Err(e) => {
+                    tracing::warn!("Ignoring unreadable key change event: {e}");
+                    continue;
+                }
+            };
+
+            match serde_json::from_str::<KeyChangeEvent>(&payload) {
+                Ok(event) => listener.key_changed(&event),
+                Err(e) => tracing::warn!("Ignoring invalid key change event: {e}"),
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -1,21 +1,129 @@
+#[cfg(feature = "axum")]
 pub mod axum_layer;
+pub mod backup;
+pub mod builder;
+pub mod clock;
+#[cfg(any(test, feature = "testing"))]
+pub mod conformance;
 pub mod errors;
+pub mod invalidation;
 pub mod limiters;
 pub mod manager;
 #[cfg(test)]
 mod mock;
+#[cfg(feature = "redis")]
+pub mod redis_connection;
+pub mod schema;
 pub mod storage;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 pub mod traits;
 pub mod types;
 
 #[cfg(test)]
 mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use async_trait::async_trait;
+    #[cfg(feature = "axum")]
+    use axum::{body::Body, routing::get, Extension, Router};
+    #[cfg(feature = "axum")]
+    use http::{Request, StatusCode};
+    use tokio::sync::Notify;
+    #[cfg(feature = "axum")]
+    use tower::ServiceExt;
+
     use super::*;
+    #[cfg(feature = "axum")]
+    use crate::axum_layer::{usage::UsageCost, ApiKeyLayer};
+    use crate::{
+        backup::{
+            export::Exporter,
+            import::{ConflictPolicy, ImportReport, Importer},
+            ExportCheckpoint, ImportCheckpoint,
+        },
+        builder::ApiKeyPlan,
+        clock::{Clock, ManualClock},
+        conformance::{limiter::check_api_key_limiter, storage::check_api_key_storage},
+        invalidation::{KeyChangeEvent, KeyChangeKind, KeyChangeListener},
+        limiters::{
+            limiter_chain::LimiterChain, memory_concurrency_limiter::MemoryConcurrencyLimiter,
+            memory_limiter::MemoryLimiter,
+        },
+        manager::{
+            failure_policy::{CircuitBreaker, FailurePolicy},
+            KeyManager,
+        },
+        mock::{
+            mock_api_key::{get_mock_api_key, get_mock_api_key_at},
+            mock_limiter::{CountingLimiter, FailingLimiter},
+            mock_storage::CountingStorage,
+        },
+        schema::{upcast, CURRENT_SCHEMA_VERSION},
+        storage::{
+            cached_storage::{CacheOptions, CachedStorage},
+            memory_storage::HashMapStorage,
+            tiered_storage::TieredStorage,
+        },
+        testing::{
+            api_key::test_api_key,
+            limiter::MockLimiter,
+            storage::{RecordingStorage, StorageCall},
+        },
+        traits::{ApiKeyLimiter, ApiKeyLister, ApiKeyManager, ApiKeyStorage},
+        types::{ApiKey, ApiKeyLimit, Lease},
+    };
+    #[cfg(feature = "file")]
+    use crate::storage::file_storage::FileStorage;
+    #[cfg(feature = "mongodb")]
+    use crate::storage::mongodb_storage::MongoDBStorage;
+    #[cfg(feature = "postgres")]
+    use crate::storage::postgres_storage::PostgresStorage;
+    #[cfg(feature = "redis")]
     use crate::{
-        mock::mock_api_key::get_mock_api_key,
-        storage::{memory_storage::HashMapStorage, mongodb_storage::MongoDBStorage},
-        traits::ApiKeyStorage,
+        invalidation::redis_invalidation::{RedisInvalidationPublisher, RedisInvalidationSubscriber},
+        limiters::{redis_concurrency_limiter::RedisConcurrencyLimiter, redis_limiter::RedisLimiter},
+        redis_connection::{RedisConnection, RedisKeyspace, RedisTimeouts},
+        storage::redis_storage::RedisStorage,
+        testing::api_key::TestApiKey,
     };
+    #[cfg(feature = "sqlite")]
+    use crate::storage::sqlite_storage::SqliteStorage;
+
+    #[cfg(feature = "axum")]
+    async fn get_test_app<L>(api_key: ApiKey, limiter: L) -> (Router, Arc<Notify>)
+    where
+        L: ApiKeyLimiter + Send + Sync + Clone + 'static,
+    {
+        let mut storage = HashMapStorage::new();
+
+        storage.store_api_key(&api_key.key, &api_key).await.expect("The key should be stored");
+
+        let release = Arc::new(Notify::new());
+        let slow_release = release.clone();
+
+        let app = Router::new()
+            .route("/cost", get(|| async { (Extension(UsageCost(5)), "ok") }))
+            .route("/free", get(|| async { (Extension(UsageCost(0)), "ok") }))
+            .route("/fail", get(|| async { (StatusCode::INTERNAL_SERVER_ERROR, Extension(UsageCost(5)), "error") }))
+            .route(
+                "/slow",
+                get(move || async move {
+                    slow_release.notified().await;
+                    "ok"
+                }),
+            )
+            .layer(ApiKeyLayer::new(KeyManager::new(storage, limiter)));
+
+        (app, release)
+    }
+
+    #[cfg(feature = "axum")]
+    async fn send_request(app: Router, uri: &str, key: &str) -> StatusCode {
+        let request = Request::builder().uri(uri).header("x-api-key", key).body(Body::empty()).unwrap();
+
+        app.oneshot(request).await.unwrap().status()
+    }
 
     #[tokio::test]
     async fn it_can_store_an_api_key() {
@@ -64,7 +172,9 @@ mod tests {
         }
     }
 
+    #[cfg(feature = "mongodb")]
     #[tokio::test]
+    #[ignore = "requires a running MongoDB, see docker-compose.yml"]
     async fn it_can_store_an_api_key_using_mongodb_storage() {
         dotenv::dotenv().ok();
         let uri = std::env::var("MONGODB_URI").expect("MONGODB_URI must be set");
@@ -87,4 +197,1249 @@ mod tests {
             Err(e) => assert_eq!(true, false, "The key should have been stored {}", e),
         }
     }
+
+    #[cfg(feature = "mongodb")]
+    #[tokio::test]
+    #[ignore = "requires a running MongoDB, see docker-compose.yml"]
+    async fn it_rejects_concurrent_duplicate_keys_using_mongodb_storage() {
+        dotenv::dotenv().ok();
+        let uri = std::env::var("MONGODB_URI").expect("MONGODB_URI must be set");
+        let db_name = std::env::var("MONGODB_DB_NAME").expect("MONGODB_DB_NAME must be set");
+
+        let mut storage = MongoDBStorage::new(&uri, &db_name, None).await.expect("Failed to create MongoDBStorage");
+
+        let key = "mongodb_duplicate_test_key";
+        let api_key = get_mock_api_key(Some(key.to_string()));
+
+        let _ = storage.delete_api_key(key).await;
+
+        let (mut first, mut second) = (storage.clone(), storage.clone());
+        let (first, second) = tokio::join!(first.store_api_key(key, &api_key), second.store_api_key(key, &api_key));
+
+        match (first, second) {
+            (Ok(_), Err(errors::ApiKeyStorageError::KeyAlreadyExists))
+            | (Err(errors::ApiKeyStorageError::KeyAlreadyExists), Ok(_)) => {}
+            results => {
+                panic!("Exactly one of the concurrent stores should succeed, got {results:?}")
+            }
+        }
+
+        let _ = storage.delete_api_key(key).await;
+    }
+
+    #[cfg(feature = "mongodb")]
+    #[tokio::test]
+    async fn it_rejects_records_for_another_key_using_mongodb_storage() {
+        // The client connects lazily, the record is rejected before reaching the server.
+        let mut storage = MongoDBStorage::connect("mongodb://localhost:1", "apikeys", None)
+            .await
+            .expect("Failed to create MongoDBStorage");
+
+        match storage.store_api_key("another_key", &get_mock_api_key(None)).await {
+            Err(errors::ApiKeyStorageError::KeyMismatch) => {}
+            result => panic!("A record for another key should be rejected, got {result:?}"),
+        }
+    }
+
+    #[cfg(feature = "axum")]
+    #[tokio::test]
+    async fn it_charges_the_usage_cost_reported_by_the_handler() {
+        let limiter = CountingLimiter::new();
+        let (app, _) = get_test_app(get_mock_api_key(None), limiter.clone()).await;
+
+        assert_eq!(send_request(app.clone(), "/cost", "test_key").await, StatusCode::OK);
+        assert_eq!(limiter.used(), 5, "The reported cost should replace the pre-charged unit");
+
+        assert_eq!(send_request(app, "/free", "test_key").await, StatusCode::OK);
+        assert_eq!(limiter.used(), 5, "A zero cost response should refund the pre-charged unit");
+    }
+
+    #[cfg(feature = "axum")]
+    #[tokio::test]
+    async fn it_rejects_keys_the_limiter_refuses() {
+        let limiter = MockLimiter::new();
+        let (app, _) = get_test_app(test_api_key("test_key"), limiter.clone()).await;
+
+        limiter.force_rate_limit();
+        assert_eq!(send_request(app.clone(), "/free", "test_key").await, StatusCode::UNAUTHORIZED);
+
+        limiter.force_error("Connection refused");
+        assert_eq!(send_request(app.clone(), "/free", "test_key").await, StatusCode::UNAUTHORIZED);
+
+        limiter.accept();
+        assert_eq!(send_request(app, "/free", "test_key").await, StatusCode::OK);
+        assert_eq!(limiter.uses(), 3);
+    }
+
+    #[cfg(feature = "axum")]
+    #[tokio::test]
+    async fn it_refunds_server_errors() {
+        let limiter = CountingLimiter::new();
+        let (app, _) = get_test_app(get_mock_api_key(None), limiter.clone()).await;
+
+        assert_eq!(send_request(app, "/fail", "test_key").await, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(limiter.used(), 0, "Server errors should not be charged");
+    }
+
+    #[cfg(feature = "axum")]
+    #[tokio::test]
+    async fn it_limits_concurrent_requests_until_the_response_is_finished() {
+        let mut api_key = get_mock_api_key(None);
+        api_key.limits.max_concurrent_requests = ApiKeyLimit::Limited(1);
+
+        let limiter = MemoryConcurrencyLimiter::new();
+        let (app, release) = get_test_app(api_key.clone(), limiter.clone()).await;
+
+        let slow_request = tokio::spawn(send_request(app.clone(), "/slow", "test_key"));
+
+        while limiter.in_flight(&api_key) == 0 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+
+        assert_eq!(send_request(app.clone(), "/cost", "test_key").await, StatusCode::UNAUTHORIZED);
+        assert_eq!(limiter.in_flight(&api_key), 1, "A rejected request should not hold a slot");
+
+        release.notify_one();
+        assert_eq!(slow_request.await.unwrap(), StatusCode::OK);
+
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while limiter.in_flight(&api_key) > 0 {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .expect("The slot should be released");
+
+        assert_eq!(send_request(app, "/cost", "test_key").await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn it_rolls_back_earlier_limiters_when_a_chained_limiter_rejects() {
+        let mut api_key = get_mock_api_key(None);
+        api_key.limits.max_concurrent_requests = ApiKeyLimit::Limited(1);
+
+        let counting_limiter = CountingLimiter::new();
+        let concurrency_limiter = MemoryConcurrencyLimiter::new();
+        let chain = LimiterChain::new().with(counting_limiter.clone()).with(concurrency_limiter.clone());
+
+        let lease = Lease::new();
+        chain.acquire_key(&api_key, &lease).await.expect("The first request should be accepted");
+
+        match chain.acquire_key(&api_key, &Lease::new()).await {
+            Err(errors::ApiKeyLimiterError::ConcurrencyLimitExceeded) => {}
+            result => {
+                panic!("The second request should exceed the concurrency limit, got {result:?}")
+            }
+        }
+
+        assert_eq!(counting_limiter.used(), 1, "The rejected request should be refunded");
+        assert_eq!(concurrency_limiter.in_flight(&api_key), 1);
+
+        chain.release_key(&api_key, &lease).await.expect("The request should be released");
+
+        assert_eq!(concurrency_limiter.in_flight(&api_key), 0);
+    }
+
+    #[tokio::test]
+    async fn it_does_not_hold_a_slot_for_keys_used_without_a_lease() {
+        let mut storage = HashMapStorage::new();
+        let mut api_key = test_api_key("test_key");
+        api_key.limits.max_concurrent_requests = ApiKeyLimit::Limited(2);
+        storage.store_api_key(&api_key.key, &api_key).await.expect("The key should be stored");
+
+        let concurrency_limiter = MemoryConcurrencyLimiter::new();
+        let chain = LimiterChain::new().with(MockLimiter::new()).with(concurrency_limiter.clone());
+        let manager = KeyManager::new(storage, chain);
+
+        for attempt in 1..=5 {
+            if let Err(e) = manager.use_key("test_key").await {
+                panic!("Use {attempt} should be accepted, got {e}");
+            }
+        }
+
+        assert_eq!(concurrency_limiter.in_flight(&api_key), 0, "Uses should not hold an in-flight slot");
+
+        let _leases = [manager.acquire_key("test_key").await.unwrap(), manager.acquire_key("test_key").await.unwrap()];
+
+        match manager.use_key("test_key").await {
+            Err(errors::ApiKeyManagerError::LimiterError(errors::ApiKeyLimiterError::ConcurrencyLimitExceeded)) => {}
+            result => panic!("A use should be rejected while every slot is held, got {result:?}"),
+        }
+    }
+
+    async fn get_test_manager(
+        limiter: FailingLimiter,
+        failure_policy: FailurePolicy,
+        clock: ManualClock,
+    ) -> impl ApiKeyManager {
+        let mut storage = HashMapStorage::new();
+        let api_key = get_mock_api_key(None);
+
+        storage.store_api_key(&api_key.key, &api_key).await.expect("The key should be stored");
+
+        KeyManager::new(storage, limiter)
+            .with_failure_policy(failure_policy)
+            .with_circuit_breaker(CircuitBreaker::new(2, Duration::from_secs(60)))
+            .with_clock(clock)
+    }
+
+    #[tokio::test]
+    async fn it_applies_the_failure_policy_when_the_limiter_is_down() {
+        let manager = get_test_manager(FailingLimiter::new(), FailurePolicy::FailClosed, ManualClock::default()).await;
+
+        match manager.use_key("test_key").await {
+            Err(errors::ApiKeyManagerError::LimiterError(errors::ApiKeyLimiterError::Other(_))) => {}
+            result => panic!("A fail-closed manager should reject the key, got {result:?}"),
+        }
+
+        let manager = get_test_manager(FailingLimiter::new(), FailurePolicy::FailOpen, ManualClock::default()).await;
+
+        assert!(manager.use_key("test_key").await.is_ok(), "A fail-open manager should accept the key");
+    }
+
+    #[tokio::test]
+    async fn it_stops_calling_a_failing_limiter_once_the_circuit_is_open() {
+        let clock = ManualClock::default();
+        let limiter = FailingLimiter::new();
+        // The mock key allows 100 reads per minute, so the fallback allows 100 / 50 = 2.
+        let fallback = MemoryLimiter::new().with_limit_divisor(50);
+        let manager = get_test_manager(limiter.clone(), FailurePolicy::fallback(fallback), clock.clone()).await;
+
+        assert!(manager.use_key("test_key").await.is_ok());
+        assert!(manager.use_key("test_key").await.is_ok());
+        assert!(manager.use_key("test_key").await.is_err(), "The fallback limiter should enforce reduced limits");
+
+        assert_eq!(limiter.calls(), 2, "The limiter should not be called once the circuit is open");
+
+        clock.advance(Duration::from_secs(60));
+
+        assert!(manager.use_key("test_key").await.is_ok(), "The fallback should accept the key in the next window");
+        assert_eq!(limiter.calls(), 3, "The limiter should be probed once the circuit is half-open");
+
+        assert!(manager.use_key("test_key").await.is_ok());
+        assert_eq!(limiter.calls(), 3, "The failed probe should open the circuit again");
+    }
+
+    #[tokio::test]
+    async fn it_ends_requests_with_the_limiter_that_admitted_them() {
+        let clock = ManualClock::default();
+        let limiter = MockLimiter::new();
+        let fallback = MemoryConcurrencyLimiter::new();
+
+        let mut storage = HashMapStorage::new();
+        let mut api_key = test_api_key("test_key");
+        api_key.limits.max_concurrent_requests = ApiKeyLimit::Limited(5);
+        storage.store_api_key(&api_key.key, &api_key).await.expect("The key should be stored");
+
+        let manager = KeyManager::new(storage, limiter.clone())
+            .with_failure_policy(FailurePolicy::fallback(fallback.clone()))
+            .with_circuit_breaker(CircuitBreaker::new(1, Duration::from_secs(60)))
+            .with_clock(clock.clone());
+
+        limiter.force_error("Connection refused");
+        let (_, fallback_lease) = manager.acquire_key("test_key").await.expect("The fallback should admit the key");
+        assert_eq!(fallback.in_flight(&api_key), 1);
+
+        limiter.accept();
+        clock.advance(Duration::from_secs(60));
+
+        manager.release_key(&api_key, &fallback_lease).await.expect("The request should be released");
+        assert_eq!(fallback.in_flight(&api_key), 0, "The fallback should free the slot it handed out");
+        assert_eq!(limiter.releases(), 0, "The limiter should not release a request it did not admit");
+
+        let (_, lease) = manager.acquire_key("test_key").await.expect("The probe should be admitted");
+        assert_eq!(limiter.uses(), 2, "Releasing a request should not use up the probe of the half-open circuit");
+
+        limiter.force_error("Connection refused");
+        assert!(manager.acquire_key("test_key").await.is_ok(), "The failure should open the circuit again");
+        assert_eq!(fallback.in_flight(&api_key), 1);
+
+        limiter.accept();
+        manager.release_key(&api_key, &lease).await.expect("The request should be released");
+        assert_eq!(limiter.releases(), 1, "The limiter should release the request it admitted");
+        assert_eq!(fallback.in_flight(&api_key), 1, "The fallback should keep the slots of its own requests");
+    }
+
+    #[test]
+    fn it_lets_a_single_probe_through_a_half_open_circuit() {
+        let circuit_breaker = CircuitBreaker::new(1, Duration::from_secs(30));
+
+        circuit_breaker.record_failure(Duration::ZERO);
+        assert!(!circuit_breaker.allows_request(Duration::from_secs(10)));
+
+        assert!(circuit_breaker.allows_request(Duration::from_secs(30)), "The first request should probe the backend");
+        assert!(!circuit_breaker.allows_request(Duration::from_secs(31)), "Only one probe should be in flight");
+
+        assert!(circuit_breaker.allows_request(Duration::from_secs(60)), "A probe that never ended should be replaced");
+
+        circuit_breaker.record_success();
+        assert!(circuit_breaker.allows_request(Duration::from_secs(61)));
+        assert!(circuit_breaker.allows_request(Duration::from_secs(61)), "A successful probe should close the circuit");
+    }
+
+    #[cfg(feature = "redis")]
+    #[test]
+    fn it_builds_hash_tagged_redis_keys_within_the_namespace() {
+        let keyspace = RedisKeyspace::new("billing-service");
+
+        assert_eq!(keyspace.key("test_key", "read_count"), "billing-service:{test_key}:read_count");
+        assert_eq!(keyspace.key("test_key", "in_flight"), "billing-service:{test_key}:in_flight");
+
+        let keyspace = RedisKeyspace::new("{billing}");
+
+        assert_eq!(keyspace.key("test_key", "read_count"), "billing:{test_key}:read_count");
+    }
+
+    #[test]
+    fn it_builds_api_keys_from_a_plan() {
+        let clock = ManualClock::default();
+
+        let api_key = ApiKey::builder()
+            .with_key_prefix("live")
+            .with_plan(ApiKeyPlan::Pro)
+            .with_concurrent_requests(ApiKeyLimit::Unlimited)
+            .with_allowed_domain("api.example.com")
+            .with_owner("billing-service")
+            .with_clock(clock.clone())
+            .build()
+            .expect("The key should be valid");
+
+        assert!(api_key.key.starts_with("live_") && api_key.key.len() == 45, "Unexpected key {}", api_key.key);
+        assert!(matches!(api_key.limits.max_reads_per_minute, ApiKeyLimit::Limited(600)));
+        assert!(matches!(api_key.limits.max_concurrent_requests, ApiKeyLimit::Unlimited));
+        assert!(matches!(api_key.status, types::ApiKeyStatus::Active));
+        assert_eq!(api_key.created_at, clock.now());
+        assert_eq!(api_key.schema_version, CURRENT_SCHEMA_VERSION);
+
+        let other_key = ApiKey::builder().build().expect("The defaults should be valid");
+        assert_ne!(api_key.key, other_key.key, "Keys should be generated randomly");
+        assert!(matches!(other_key.limits.max_reads_per_minute, ApiKeyLimit::Limited(60)));
+    }
+
+    #[test]
+    fn it_rejects_invalid_api_keys() {
+        use errors::ApiKeyValidationError;
+
+        let cases = [
+            (ApiKey::builder().with_key("short_key"), ApiKeyValidationError::KeyTooShort { min_length: 16 }),
+            (ApiKey::builder().with_key("a key with spaces"), ApiKeyValidationError::InvalidKeyCharacter(' ')),
+            (ApiKey::builder().with_key_prefix("live_"), ApiKeyValidationError::InvalidKeyPrefix("live_".to_string())),
+            (
+                ApiKey::builder().with_key("imported_service_key").with_key_prefix("live"),
+                ApiKeyValidationError::PrefixWithExplicitKey,
+            ),
+            (
+                ApiKey::builder().with_writes_per_minute(ApiKeyLimit::Limited(0)),
+                ApiKeyValidationError::ZeroLimit("max_writes_per_minute"),
+            ),
+            (
+                ApiKey::builder().with_allowed_domain("https://example.com"),
+                ApiKeyValidationError::InvalidDomain("https://example.com".to_string()),
+            ),
+            (
+                ApiKey::builder().with_allowed_domain("-example.com"),
+                ApiKeyValidationError::InvalidDomain("-example.com".to_string()),
+            ),
+            (ApiKey::builder().with_owner(" "), ApiKeyValidationError::EmptyOwner),
+        ];
+
+        for (builder, expected) in cases {
+            match builder.build() {
+                Err(error) => assert_eq!(error, expected),
+                Ok(api_key) => panic!("Expected {expected}, the key {} was built", api_key.key),
+            }
+        }
+
+        assert!(ApiKey::builder().with_key("imported_service_key").build().is_ok());
+    }
+
+    #[test]
+    fn it_derives_a_stable_hash_from_the_api_key() {
+        let api_key = get_mock_api_key(None);
+
+        assert_eq!(api_key.key_hash(), "92488e1e3eeecdf99f3ed2ce59233efb4b4fb612d5655c0ce9ea52b5a502e655");
+        assert_eq!(api_key.key_hash(), ApiKey::hash_key("test_key"));
+    }
+
+    #[cfg(feature = "postgres")]
+    async fn get_postgres_storage() -> PostgresStorage {
+        dotenv::dotenv().ok();
+        let uri = std::env::var("POSTGRES_URI").expect("POSTGRES_URI must be set");
+
+        let storage = PostgresStorage::new(&uri).await.expect("Failed to create PostgresStorage");
+        storage.migrate().await.expect("Failed to run the Postgres migrations");
+
+        storage
+    }
+
+    #[cfg(feature = "postgres")]
+    #[tokio::test]
+    #[ignore = "requires a running Postgres, see docker-compose.yml"]
+    async fn it_can_store_and_retrieve_an_api_key_using_postgres_storage() {
+        let mut storage = get_postgres_storage().await;
+
+        let key = "postgres_test_key";
+        let api_key = get_mock_api_key(Some(key.to_string()));
+
+        let _ = storage.delete_api_key(key).await;
+
+        let stored_key = storage.store_api_key(key, &api_key).await.expect("The key should have been stored");
+        assert_eq!(key, stored_key, "The stored key should match the key that was passed in");
+
+        let retrieved_api_key = storage.retrieve_api_key(key).await.expect("The key should have been found");
+        assert_eq!(api_key.key, retrieved_api_key.key);
+        assert_eq!(
+            api_key.limits.max_reads_per_minute.to_string(),
+            retrieved_api_key.limits.max_reads_per_minute.to_string()
+        );
+        assert_eq!(api_key.restrictions.allowed_domains, retrieved_api_key.restrictions.allowed_domains);
+        assert!(matches!(retrieved_api_key.status, types::ApiKeyStatus::Active));
+    }
+
+    #[cfg(feature = "postgres")]
+    #[tokio::test]
+    #[ignore = "requires a running Postgres, see docker-compose.yml"]
+    async fn it_rejects_duplicate_keys_using_postgres_storage() {
+        let mut storage = get_postgres_storage().await;
+
+        let key = "postgres_duplicate_key";
+        let api_key = get_mock_api_key(Some(key.to_string()));
+
+        let _ = storage.delete_api_key(key).await;
+
+        storage.store_api_key(key, &api_key).await.expect("The key should have been stored");
+
+        match storage.store_api_key(key, &api_key).await {
+            Err(errors::ApiKeyStorageError::KeyAlreadyExists) => {}
+            result => panic!("Storing the same key twice should fail, got {result:?}"),
+        }
+    }
+
+    #[cfg(feature = "postgres")]
+    #[tokio::test]
+    #[ignore = "requires a running Postgres, see docker-compose.yml"]
+    async fn it_can_delete_an_api_key_using_postgres_storage() {
+        let mut storage = get_postgres_storage().await;
+
+        let key = "postgres_deleted_key";
+        let api_key = get_mock_api_key(Some(key.to_string()));
+
+        let _ = storage.delete_api_key(key).await;
+
+        storage.store_api_key(key, &api_key).await.expect("The key should have been stored");
+
+        assert!(storage.delete_api_key(key).await.expect("The key should have been deleted"));
+        assert!(!storage.delete_api_key(key).await.expect("Deleting a missing key should not fail"));
+
+        match storage.retrieve_api_key(key).await {
+            Err(errors::ApiKeyStorageError::KeyNotFound) => {}
+            result => panic!("A deleted key should not be found, got {result:?}"),
+        }
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn it_can_store_and_retrieve_an_api_key_using_sqlite_storage() {
+        let mut storage = SqliteStorage::new(":memory:").await.expect("Failed to create SqliteStorage");
+
+        let key = "test_key";
+        let mut api_key = get_mock_api_key(Some(key.to_string()));
+        api_key.owner = Some("billing-service".to_string());
+
+        let stored_key = storage.store_api_key(key, &api_key).await.expect("The key should have been stored");
+        assert_eq!(key, stored_key, "The stored key should match the key that was passed in");
+
+        match storage.store_api_key(key, &api_key).await {
+            Err(errors::ApiKeyStorageError::KeyAlreadyExists) => {}
+            result => panic!("Storing the same key twice should fail, got {result:?}"),
+        }
+
+        let retrieved_api_key = storage.retrieve_api_key(key).await.expect("The key should have been found");
+        assert_eq!(api_key.key, retrieved_api_key.key);
+        assert_eq!(api_key.created_at, retrieved_api_key.created_at);
+        assert_eq!(api_key.owner, retrieved_api_key.owner);
+
+        assert!(storage.delete_api_key(key).await.expect("The key should have been deleted"));
+
+        match storage.retrieve_api_key(key).await {
+            Err(errors::ApiKeyStorageError::KeyNotFound) => {}
+            result => panic!("A deleted key should not be found, got {result:?}"),
+        }
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn it_persists_api_keys_in_a_sqlite_file() {
+        let path = std::env::temp_dir().join(format!("apikeys-{}.sqlite", std::process::id()));
+        let path = path.to_str().expect("The temporary path should be valid UTF-8");
+
+        let mut storage = SqliteStorage::new(path).await.expect("Failed to create SqliteStorage");
+        storage.store_api_key("test_key", &get_mock_api_key(None)).await.expect("The key should have been stored");
+        drop(storage);
+
+        let storage = SqliteStorage::new(path).await.expect("Failed to reopen SqliteStorage");
+        let result = storage.retrieve_api_key("test_key").await;
+
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{path}{suffix}"));
+        }
+
+        assert!(result.is_ok(), "The key should survive reopening the database");
+    }
+
+    #[cfg(feature = "redis")]
+    #[tokio::test]
+    #[ignore = "requires a running Redis, see docker-compose.yml"]
+    async fn it_only_releases_the_lease_of_the_request_using_redis() {
+        dotenv::dotenv().ok();
+        let uri = std::env::var("REDIS_URI").expect("REDIS_URI must be set");
+
+        let limiter = RedisConcurrencyLimiter::new(&uri, Some(Duration::from_millis(100)))
+            .await
+            .expect("Failed to create RedisConcurrencyLimiter")
+            .with_keyspace(RedisKeyspace::new("apikeys-test"));
+
+        let api_key = TestApiKey::new(format!("redis_lease_test_key_{}", Lease::new().id()))
+            .with_concurrent_requests(ApiKeyLimit::Limited(1))
+            .build();
+
+        let (expired, current) = (Lease::new(), Lease::new());
+        limiter.acquire_key(&api_key, &expired).await.expect("The first request should be accepted");
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        limiter.acquire_key(&api_key, &current).await.expect("The expired lease should have been reclaimed");
+
+        limiter.release_key(&api_key, &expired).await.expect("The expired lease should be released");
+
+        match limiter.acquire_key(&api_key, &Lease::new()).await {
+            Err(errors::ApiKeyLimiterError::ConcurrencyLimitExceeded) => {}
+            result => panic!("The current request should still hold its slot, got {result:?}"),
+        }
+
+        limiter.release_key(&api_key, &current).await.expect("The current lease should be released");
+        assert!(limiter.acquire_key(&api_key, &Lease::new()).await.is_ok());
+    }
+
+    #[cfg(feature = "redis")]
+    #[tokio::test]
+    #[ignore = "requires a running Redis, see docker-compose.yml"]
+    async fn it_can_store_and_list_api_keys_using_redis_storage() {
+        dotenv::dotenv().ok();
+        let uri = std::env::var("REDIS_URI").expect("REDIS_URI must be set");
+
+        let mut storage = RedisStorage::new(&uri)
+            .await
+            .expect("Failed to create RedisStorage")
+            .with_keyspace(RedisKeyspace::new("apikeys-test"));
+
+        let key = "redis_test_key";
+        let mut api_key = get_mock_api_key(Some(key.to_string()));
+        api_key.owner = Some("redis-test-owner".to_string());
+
+        let _ = storage.delete_api_key(key).await;
+
+        match storage.store_api_key("another_key", &api_key).await {
+            Err(errors::ApiKeyStorageError::KeyMismatch) => {}
+            result => panic!("A record for another key should be rejected, got {result:?}"),
+        }
+
+        storage.store_api_key(key, &api_key).await.expect("The key should have been stored");
+
+        match storage.store_api_key(key, &api_key).await {
+            Err(errors::ApiKeyStorageError::KeyAlreadyExists) => {}
+            result => panic!("Storing the same key twice should fail, got {result:?}"),
+        }
+
+        let owned_keys = storage.list_api_keys_by_owner("redis-test-owner").await.expect("The keys should be listed");
+        assert_eq!(owned_keys.len(), 1);
+        assert_eq!(owned_keys[0].key, key);
+
+        let active_keys =
+            storage.list_api_keys_by_status(&types::ApiKeyStatus::Active).await.expect("The keys should be listed");
+        assert!(active_keys.iter().any(|api_key| api_key.key == key));
+
+        assert!(storage.delete_api_key(key).await.expect("The key should have been deleted"));
+
+        let owned_keys = storage.list_api_keys_by_owner("redis-test-owner").await.expect("The keys should be listed");
+        assert!(owned_keys.is_empty(), "Deleted keys should be removed from the indexes");
+    }
+
+    #[cfg(feature = "file")]
+    const KEY_FILE: &str = "
+keys:
+  - key: internal_service_key
+    owner: reporting
+    limits:
+      max_reads_per_minute: !Limited 100
+      max_writes_per_minute: Unlimited
+";
+
+    #[cfg(feature = "file")]
+    #[tokio::test]
+    async fn it_serves_api_keys_defined_in_a_file() {
+        let path = std::env::temp_dir().join(format!("apikeys-{}-serve.yaml", std::process::id()));
+        std::fs::write(&path, KEY_FILE).expect("The key file should be written");
+
+        let clock = ManualClock::default();
+        let mut storage = FileStorage::with_clock(&path, clock.clone()).await.expect("The key file should be valid");
+        let _ = std::fs::remove_file(&path);
+
+        let api_key = storage.retrieve_api_key("internal_service_key").await.expect("The key should have been found");
+        assert_eq!(api_key.owner.as_deref(), Some("reporting"));
+        assert!(matches!(api_key.status, types::ApiKeyStatus::Active));
+        assert_eq!(api_key.created_at, clock.now(), "Missing timestamps should be the load time");
+
+        match storage.store_api_key("test_key", &get_mock_api_key(None)).await {
+            Err(errors::ApiKeyStorageError::ReadOnly) => {}
+            result => panic!("A file storage should be read-only, got {result:?}"),
+        }
+    }
+
+    #[cfg(feature = "file")]
+    #[tokio::test]
+    async fn it_reloads_a_key_file_when_it_changes() {
+        let path = std::env::temp_dir().join(format!("apikeys-{}-reload.yaml", std::process::id()));
+        std::fs::write(&path, KEY_FILE).expect("The key file should be written");
+
+        let storage = FileStorage::new(&path).await.expect("The key file should be valid");
+        let watcher = storage.watch(Duration::from_millis(10));
+
+        let duplicated_keys = format!("{KEY_FILE}{}", KEY_FILE.replace("keys:", ""));
+        std::fs::write(&path, duplicated_keys).expect("The key file should be written");
+
+        match FileStorage::new(&path).await {
+            Err(errors::FileStorageError::InvalidKey { index: 1, .. }) => {}
+            Err(e) => panic!("The duplicated key should be reported, got {e}"),
+            Ok(_) => panic!("The duplicated key should be reported"),
+        }
+
+        std::fs::write(&path, KEY_FILE.replace("internal_service_key", "rotated_service_key"))
+            .expect("The key file should be written");
+
+        let reloaded = tokio::time::timeout(Duration::from_secs(1), async {
+            while storage.retrieve_api_key("rotated_service_key").await.is_err() {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await;
+
+        watcher.abort();
+        let _ = std::fs::remove_file(&path);
+
+        assert!(reloaded.is_ok(), "The rotated key should be served after the file changed");
+        assert!(storage.retrieve_api_key("internal_service_key").await.is_err(), "The old key should be gone");
+    }
+
+    #[tokio::test]
+    async fn it_caches_found_and_missing_keys_until_they_change() {
+        let storage = CountingStorage::new();
+        let mut cached_storage = CachedStorage::new(storage.clone(), CacheOptions::default());
+
+        cached_storage.store_api_key("test_key", &get_mock_api_key(None)).await.expect("The key should be stored");
+
+        assert!(cached_storage.retrieve_api_key("test_key").await.is_ok());
+        assert!(cached_storage.retrieve_api_key("test_key").await.is_ok());
+        assert!(cached_storage.retrieve_api_key("unknown_key").await.is_err());
+        assert!(cached_storage.retrieve_api_key("unknown_key").await.is_err());
+        assert_eq!(storage.retrievals(), 2, "Found and missing keys should both be cached");
+        assert_eq!(
+            storage.calls(),
+            vec![
+                StorageCall::Store("test_key".to_string()),
+                StorageCall::Retrieve("test_key".to_string()),
+                StorageCall::Retrieve("unknown_key".to_string()),
+            ]
+        );
+
+        cached_storage.delete_api_key("test_key").await.expect("The key should be deleted");
+
+        match cached_storage.retrieve_api_key("test_key").await {
+            Err(errors::ApiKeyStorageError::KeyNotFound) => {}
+            result => panic!("A deleted key should not be served from the cache, got {result:?}"),
+        }
+
+        let unknown_key = get_mock_api_key(Some("unknown_key".to_string()));
+        cached_storage.store_api_key("unknown_key", &unknown_key).await.expect("The key should be stored");
+
+        let retrieved = cached_storage.retrieve_api_key("unknown_key").await;
+        assert!(retrieved.is_ok(), "A stored key should not be cached as missing");
+    }
+
+    #[tokio::test]
+    async fn it_serves_stale_keys_while_refreshing_them() {
+        let clock = ManualClock::default();
+        let storage = CountingStorage::new();
+        let options = CacheOptions {
+            ttl: Duration::from_secs(30),
+            stale_ttl: Duration::from_secs(60),
+            ..Default::default()
+        };
+        let mut cached_storage = CachedStorage::new(storage.clone(), options).with_clock(clock.clone());
+
+        let api_key = get_mock_api_key_at(None, &clock);
+        cached_storage.store_api_key("test_key", &api_key).await.expect("The key should be stored");
+
+        assert!(cached_storage.retrieve_api_key("test_key").await.is_ok());
+        clock.advance(Duration::from_secs(29));
+        assert!(cached_storage.retrieve_api_key("test_key").await.is_ok());
+        assert_eq!(storage.retrievals(), 1, "The key should be served from the cache until the ttl");
+
+        clock.advance(Duration::from_secs(2));
+        assert!(cached_storage.retrieve_api_key("test_key").await.is_ok(), "The stale key should be served");
+
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while storage.retrievals() < 2 {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .expect("The stale key should be refreshed in the background");
+
+        clock.advance(Duration::from_secs(91));
+        assert!(cached_storage.retrieve_api_key("test_key").await.is_ok());
+        assert_eq!(storage.retrievals(), 3, "Keys older than the stale ttl should be fetched again right away");
+    }
+
+    #[tokio::test]
+    async fn it_expires_cached_keys_when_the_system_time_goes_back() {
+        let clock = ManualClock::default();
+        let storage = RecordingStorage::new();
+        let options = CacheOptions { ttl: Duration::from_secs(30), stale_ttl: Duration::ZERO, ..Default::default() };
+        // The cache gets its clock from the manager.
+        let manager = KeyManager::new(CachedStorage::new(storage.clone(), options), MemoryLimiter::new())
+            .with_clock(clock.clone());
+
+        let api_key = test_api_key("test_key");
+        storage.clone().store_api_key(&api_key.key, &api_key).await.expect("The key should be stored");
+
+        assert!(manager.get_key("test_key").await.is_ok());
+
+        clock.set(clock.now() - chrono::Duration::hours(1));
+        clock.advance(Duration::from_secs(31));
+
+        assert!(manager.get_key("test_key").await.is_ok());
+        assert_eq!(storage.retrievals(), 2, "The cached key should expire after its ttl whatever the time of day");
+    }
+
+    #[tokio::test]
+    async fn it_evicts_the_oldest_cached_keys() {
+        let storage = CountingStorage::new();
+        let options = CacheOptions { max_entries: 1, ..Default::default() };
+        let cached_storage = CachedStorage::new(storage.clone(), options);
+
+        let _ = cached_storage.retrieve_api_key("first_key").await;
+        let _ = cached_storage.retrieve_api_key("second_key").await;
+        let _ = cached_storage.retrieve_api_key("first_key").await;
+
+        assert_eq!(storage.retrievals(), 3, "The first key should have been evicted");
+    }
+
+    #[tokio::test]
+    async fn it_evicts_cached_keys_on_key_change_events() {
+        let storage = CountingStorage::new();
+        let mut cached_storage = CachedStorage::new(storage.clone(), CacheOptions::default());
+
+        let api_key = get_mock_api_key(None);
+        cached_storage.store_api_key(&api_key.key, &api_key).await.expect("The key should be stored");
+
+        let _ = cached_storage.retrieve_api_key(&api_key.key).await;
+        cached_storage.key_changed(&KeyChangeEvent::new(api_key.key_hash(), KeyChangeKind::Updated));
+        let _ = cached_storage.retrieve_api_key(&api_key.key).await;
+        assert_eq!(storage.retrievals(), 2, "The changed key should have been fetched again");
+
+        cached_storage.resync();
+        let _ = cached_storage.retrieve_api_key(&api_key.key).await;
+        assert_eq!(storage.retrievals(), 3, "A resync should drop every cached key");
+    }
+
+    #[cfg(feature = "redis")]
+    #[tokio::test]
+    #[ignore = "requires a running Redis, see docker-compose.yml"]
+    async fn it_invalidates_caches_on_other_nodes_through_redis() {
+        dotenv::dotenv().ok();
+        let uri = std::env::var("REDIS_URI").expect("REDIS_URI must be set");
+        let channel = format!("apikeys-test:invalidation:{}", std::process::id());
+
+        let connection =
+            RedisConnection::connect(&uri, RedisTimeouts::default()).await.expect("Failed to connect to redis");
+        let storage =
+            RedisStorage::from_connection(connection.clone()).with_keyspace(RedisKeyspace::new("apikeys-test"));
+
+        let mut writer = RedisInvalidationPublisher::new(storage.clone(), connection).with_channel(&channel);
+        let reader = CachedStorage::new(storage, CacheOptions::default());
+
+        let subscriber = RedisInvalidationSubscriber::new(&uri)
+            .expect("The uri should be valid")
+            .with_channel(&channel)
+            .spawn(reader.clone());
+
+        let key = "redis_invalidation_test_key";
+        let _ = writer.delete_api_key(key).await;
+        writer.store_api_key(key, &get_mock_api_key(Some(key.to_string()))).await.expect("The key should be stored");
+
+        // Give the subscriber time to subscribe, its initial resync would hide a missed event.
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(reader.retrieve_api_key(key).await.is_ok());
+
+        writer.delete_api_key(key).await.expect("The key should be deleted");
+
+        let evicted = tokio::time::timeout(Duration::from_secs(1), async {
+            while reader.retrieve_api_key(key).await.is_ok() {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await;
+
+        subscriber.abort();
+
+        assert!(evicted.is_ok(), "The deleted key should have been evicted from the other cache");
+    }
+
+    #[cfg(feature = "mongodb")]
+    #[tokio::test]
+    #[ignore = "requires a MongoDB replica set, see docker-compose.yml"]
+    async fn it_invalidates_cached_keys_from_the_mongodb_change_stream() {
+        dotenv::dotenv().ok();
+        let uri = std::env::var("MONGODB_REPLICA_SET_URI").expect("MONGODB_REPLICA_SET_URI must be set");
+        let db_name = std::env::var("MONGODB_DB_NAME").expect("MONGODB_DB_NAME must be set");
+
+        let mut storage = MongoDBStorage::new(&uri, &db_name, Some("api_keys_change_stream_test".to_string()))
+            .await
+            .expect("Failed to create MongoDBStorage");
+        storage.enable_change_stream_pre_images().await.expect("Pre-images should be enabled");
+
+        let cached_storage = CachedStorage::new(storage.clone(), CacheOptions::default());
+        let watcher = storage.watch_changes(cached_storage.clone());
+
+        let key = "mongodb_change_stream_test_key";
+        let _ = storage.delete_api_key(key).await;
+
+        // Give the watcher time to open the stream, its initial resync would hide a missed event.
+        tokio::time::sleep(Duration::from_millis(500)).await;
+
+        assert!(cached_storage.retrieve_api_key(key).await.is_err(), "The missing key should be cached");
+        storage.store_api_key(key, &get_mock_api_key(Some(key.to_string()))).await.expect("The key should be stored");
+
+        let stored = tokio::time::timeout(Duration::from_secs(2), async {
+            while cached_storage.retrieve_api_key(key).await.is_err() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await;
+        assert!(stored.is_ok(), "The inserted key should have been evicted from the negative cache");
+
+        storage.delete_api_key(key).await.expect("The key should be deleted");
+
+        let deleted = tokio::time::timeout(Duration::from_secs(2), async {
+            while cached_storage.retrieve_api_key(key).await.is_ok() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await;
+
+        watcher.abort();
+
+        assert!(deleted.is_ok(), "The deleted key should have been evicted from the cache");
+    }
+
+    #[tokio::test]
+    async fn it_reads_through_the_fast_tier_and_survives_a_durable_outage() {
+        let (fast, mut durable) = (CountingStorage::new(), CountingStorage::new());
+        let storage = TieredStorage::new(fast.clone(), durable.clone());
+
+        let api_key = get_mock_api_key(None);
+        durable.store_api_key(&api_key.key, &api_key).await.expect("The key should be stored");
+
+        assert!(storage.retrieve_api_key(&api_key.key).await.is_ok());
+        assert!(fast.retrieve_api_key(&api_key.key).await.is_ok(), "The key should have been copied to the fast tier");
+
+        durable.set_available(false);
+
+        assert!(storage.retrieve_api_key(&api_key.key).await.is_ok(), "The fast tier should keep serving the key");
+        assert!(storage.retrieve_api_key("unknown_key").await.is_err());
+        assert_eq!(durable.retrievals(), 2, "Only misses should reach the durable tier");
+    }
+
+    #[tokio::test]
+    async fn it_writes_through_both_tiers() {
+        let (fast, durable) = (CountingStorage::new(), CountingStorage::new());
+        let mut storage = TieredStorage::new(fast.clone(), durable.clone());
+
+        let api_key = get_mock_api_key(None);
+        storage.store_api_key(&api_key.key, &api_key).await.expect("The key should be stored");
+
+        assert!(fast.retrieve_api_key(&api_key.key).await.is_ok());
+        assert!(durable.retrieve_api_key(&api_key.key).await.is_ok());
+
+        fast.set_available(false);
+
+        let deleted = storage.delete_api_key(&api_key.key).await;
+        assert!(deleted.is_err(), "A key left in the fast tier should fail the delete");
+
+        fast.set_available(true);
+
+        assert!(storage.delete_api_key(&api_key.key).await.expect("The key should be deleted"));
+        assert!(fast.retrieve_api_key(&api_key.key).await.is_err());
+        assert!(durable.retrieve_api_key(&api_key.key).await.is_err());
+    }
+
+    /// Durable tier whose reads wait for `resume` once they have read the key.
+    #[derive(Clone)]
+    struct PausedStorage {
+        inner: HashMapStorage,
+        read: Arc<Notify>,
+        resume: Arc<Notify>,
+    }
+
+    #[async_trait]
+    impl ApiKeyStorage for PausedStorage {
+        async fn store_api_key(&mut self, key: &str, value: &ApiKey) -> Result<String, errors::ApiKeyStorageError> {
+            self.inner.store_api_key(key, value).await
+        }
+
+        async fn retrieve_api_key(&self, key: &str) -> Result<ApiKey, errors::ApiKeyStorageError> {
+            let result = self.inner.retrieve_api_key(key).await;
+
+            self.read.notify_one();
+            self.resume.notified().await;
+
+            result
+        }
+
+        async fn delete_api_key(&mut self, key: &str) -> Result<bool, errors::ApiKeyStorageError> {
+            self.inner.delete_api_key(key).await
+        }
+    }
+
+    #[tokio::test]
+    async fn it_does_not_copy_back_a_key_deleted_while_it_is_read() {
+        let (fast, mut inner) = (RecordingStorage::new(), HashMapStorage::new());
+        let (read, resume) = (Arc::new(Notify::new()), Arc::new(Notify::new()));
+        let durable = PausedStorage { inner: inner.clone(), read: read.clone(), resume: resume.clone() };
+        let mut storage = TieredStorage::new(fast.clone(), durable);
+
+        let api_key = test_api_key("test_key");
+        inner.store_api_key(&api_key.key, &api_key).await.expect("The key should be stored");
+
+        let reader = storage.clone();
+        let key = api_key.key.clone();
+        let retrieval = tokio::spawn(async move { reader.retrieve_api_key(&key).await });
+
+        read.notified().await;
+        assert!(storage.delete_api_key(&api_key.key).await.expect("The key should be deleted"));
+        resume.notify_one();
+
+        assert!(retrieval.await.expect("The read should finish").is_ok(), "The read started before the delete");
+        assert!(fast.retrieve_api_key(&api_key.key).await.is_err(), "The deleted key should not be in the fast tier");
+    }
+
+    async fn get_backup_source() -> HashMapStorage {
+        let mut storage = HashMapStorage::new();
+
+        for key in ["first_key", "second_key", "third_key"] {
+            let api_key = get_mock_api_key(Some(key.to_string()));
+            storage.store_api_key(key, &api_key).await.expect("The key should be stored");
+        }
+
+        storage
+    }
+
+    fn dumped_keys(dump: &[u8]) -> Vec<String> {
+        String::from_utf8_lossy(dump)
+            .lines()
+            .skip(1)
+            .map(|line| serde_json::from_str::<ApiKey>(line).expect("The dumped key should be valid").key)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn it_lists_api_keys_in_hash_order() {
+        let mut storage = HashMapStorage::new();
+
+        for key in ["list_key_a", "list_key_b", "list_key_c", "list_key_d", "list_key_e"] {
+            storage.store_api_key(key, &test_api_key(key)).await.expect("The key should be stored");
+        }
+
+        storage.delete_api_key("list_key_c").await.expect("The key should be deleted");
+
+        let mut listed = Vec::new();
+        let mut cursor = None;
+
+        loop {
+            let page = storage.list_api_keys(cursor.as_deref(), 2).await.expect("The keys should be listed");
+            listed.extend(page.api_keys.iter().map(ApiKey::key_hash));
+
+            match page.next_cursor {
+                Some(next_cursor) => cursor = Some(next_cursor),
+                None => break,
+            }
+        }
+
+        let mut expected: Vec<String> =
+            ["list_key_a", "list_key_b", "list_key_d", "list_key_e"].into_iter().map(ApiKey::hash_key).collect();
+        expected.sort();
+
+        assert_eq!(listed, expected, "Every remaining key should be listed once, in hash order");
+    }
+
+    #[tokio::test]
+    async fn it_resumes_an_interrupted_export() {
+        let exporter = Exporter::new(get_backup_source().await).with_batch_size(2);
+
+        let mut checkpoints = Vec::new();
+        let mut dump = Vec::new();
+        let report = exporter.run(&mut dump, |checkpoint| checkpoints.push(checkpoint.clone())).await.unwrap();
+
+        assert_eq!(report.exported, 3);
+        assert_eq!(dumped_keys(&dump).len(), 3);
+        assert_eq!(checkpoints.len(), 1);
+
+        let checkpoint: ExportCheckpoint = checkpoints.remove(0);
+        let mut resumed_dump = dump[..checkpoint.bytes as usize].to_vec();
+
+        let exporter = Exporter::new(get_backup_source().await).with_batch_size(2).resume_from(checkpoint);
+        let report = exporter.run(&mut resumed_dump, |_| {}).await.unwrap();
+
+        assert_eq!(report.exported, 1);
+        assert_eq!(dumped_keys(&resumed_dump), dumped_keys(&dump), "The resumed export should complete the dump");
+    }
+
+    #[tokio::test]
+    async fn it_imports_a_dump_with_conflict_policies() {
+        let mut dump = Vec::new();
+        Exporter::new(get_backup_source().await).run(&mut dump, |_| {}).await.unwrap();
+
+        let storage = HashMapStorage::new();
+
+        let report = Importer::new(storage.clone()).with_dry_run(true).run(dump.as_slice(), |_| {}).await.unwrap();
+        assert_eq!(report, ImportReport { imported: 3, ..Default::default() });
+        assert!(storage.retrieve_api_key("first_key").await.is_err(), "A dry run should not write");
+
+        let mut importer = Importer::new(storage.clone()).resume_from(ImportCheckpoint { records: 2 });
+        let report = importer.run(dump.as_slice(), |_| {}).await.unwrap();
+        assert_eq!(report, ImportReport { imported: 1, ..Default::default() }, "Processed keys should be skipped");
+
+        let mut importer = Importer::new(storage.clone()).with_conflict_policy(ConflictPolicy::Skip);
+        let report = importer.run(dump.as_slice(), |_| {}).await.unwrap();
+        assert_eq!(report, ImportReport { imported: 2, skipped: 1, ..Default::default() });
+
+        let mut importer = Importer::new(storage.clone()).with_conflict_policy(ConflictPolicy::Overwrite);
+        let report = importer.run(dump.as_slice(), |_| {}).await.unwrap();
+        assert_eq!(report, ImportReport { overwritten: 3, ..Default::default() });
+
+        match Importer::new(storage).run(dump.as_slice(), |_| {}).await {
+            Err(errors::BackupError::Conflict { line: 2, .. }) => {}
+            result => panic!("The first existing key should fail the import, got {result:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn it_rejects_dumps_from_newer_versions() {
+        let dump = r#"{"format":"apikeys-dump","version":99,"exported_at":"2025-01-01T00:00:00Z"}"#;
+
+        match Importer::new(HashMapStorage::new()).run(dump.as_bytes(), |_| {}).await {
+            Err(errors::BackupError::UnsupportedVersion(99)) => {}
+            result => panic!("A newer dump should be rejected, got {result:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn it_keeps_the_source_of_backend_errors() {
+        let dump = "{\"format\":\"apikeys-dump\",\"version\":1,\"exported_at\":\"2025-01-01T00:00:00Z\"}\n{not json\n";
+
+        let error = Importer::new(HashMapStorage::new()).run(dump.as_bytes(), |_| {}).await.unwrap_err();
+        let source = std::error::Error::source(&error).expect("The parse error should be the source");
+        assert!(source.downcast_ref::<serde_json::Error>().is_some(), "The parse error should keep its type");
+
+        let manager = get_test_manager(FailingLimiter::new(), FailurePolicy::FailClosed, ManualClock::default()).await;
+
+        let error = manager.use_key("test_key").await.unwrap_err();
+        assert_eq!(error.to_string(), "Limiter error", "The backend error should only be reported as the source");
+
+        let source = std::error::Error::source(&error).expect("The limiter error should be the source");
+        assert_eq!(source.to_string(), "Connection refused");
+        assert_eq!(errors::ErrorChain(&error).to_string(), "Limiter error: Connection refused");
+    }
+
+    #[cfg(feature = "postgres")]
+    #[tokio::test]
+    #[ignore = "requires a running Postgres, see docker-compose.yml"]
+    async fn it_exports_and_imports_between_storages_using_postgres_storage() {
+        let mut storage = get_postgres_storage().await;
+
+        for key in ["first_key", "second_key", "third_key"] {
+            let _ = storage.delete_api_key(key).await;
+        }
+
+        let mut dump = Vec::new();
+        Exporter::new(get_backup_source().await).run(&mut dump, |_| {}).await.unwrap();
+        Importer::new(storage.clone()).run(dump.as_slice(), |_| {}).await.expect("The dump should be imported");
+
+        let mut exported = Vec::new();
+        Exporter::new(storage.clone()).with_batch_size(1).run(&mut exported, |_| {}).await.unwrap();
+
+        for key in dumped_keys(&dump) {
+            assert!(dumped_keys(&exported).contains(&key), "{key} should have been exported back");
+            let _ = storage.delete_api_key(&key).await;
+        }
+    }
+
+    fn read_fixture(fixture: &str) -> serde_json::Value {
+        serde_json::from_str(fixture).expect("The fixture should be valid JSON")
+    }
+
+    #[test]
+    fn it_upcasts_unversioned_records() {
+        let upcasted = upcast(read_fixture(include_str!("../fixtures/schema/v0.json"))).expect("v0 should be upcast");
+
+        assert_eq!(upcasted.stored_version, 0);
+        assert!(upcasted.is_outdated());
+        assert_eq!(upcasted.api_key.key, "legacy_key");
+        assert_eq!(upcasted.api_key.schema_version, CURRENT_SCHEMA_VERSION);
+        assert!(matches!(upcasted.api_key.limits.max_concurrent_requests, ApiKeyLimit::Unlimited));
+        assert_eq!(upcasted.api_key.owner, None);
+    }
+
+    #[test]
+    fn it_reads_current_records_as_they_are() {
+        let upcasted = upcast(read_fixture(include_str!("../fixtures/schema/v1.json"))).expect("v1 should be read");
+
+        assert_eq!(upcasted.stored_version, 1);
+        assert!(!upcasted.is_outdated());
+        assert!(matches!(upcasted.api_key.limits.max_concurrent_requests, ApiKeyLimit::Limited(10)));
+        assert_eq!(upcasted.api_key.owner.as_deref(), Some("billing-service"));
+    }
+
+    #[test]
+    fn it_rejects_records_from_newer_versions() {
+        let mut record = read_fixture(include_str!("../fixtures/schema/v1.json"));
+        record["schema_version"] = serde_json::json!(CURRENT_SCHEMA_VERSION + 1);
+
+        match upcast(record) {
+            Err(errors::ApiKeyStorageError::SerializationError(_)) => {}
+            result => panic!("A newer record should be rejected, got {result:?}"),
+        }
+    }
+
+    #[cfg(feature = "mongodb")]
+    #[tokio::test]
+    #[ignore = "requires a running MongoDB, see docker-compose.yml"]
+    async fn it_rewrites_outdated_records_using_mongodb_storage() {
+        dotenv::dotenv().ok();
+        let uri = std::env::var("MONGODB_URI").expect("MONGODB_URI must be set");
+        let db_name = std::env::var("MONGODB_DB_NAME").expect("MONGODB_DB_NAME must be set");
+        let collection_name = "api_keys_schema_test";
+
+        let client = mongodb::Client::with_uri_str(&uri).await.expect("Failed to connect to MongoDB");
+        let collection = client.database(&db_name).collection::<bson::Document>(collection_name);
+        let _ = collection.delete_many(bson::doc! { "key": "legacy_key" }).await;
+
+        let legacy = bson::to_document(&read_fixture(include_str!("../fixtures/schema/v0.json"))).unwrap();
+        collection.insert_one(legacy).await.expect("The legacy record should be inserted");
+
+        let storage = MongoDBStorage::new(&uri, &db_name, Some(collection_name.to_string()))
+            .await
+            .expect("Failed to create MongoDBStorage")
+            .with_outdated_record_rewrites(true);
+
+        let api_key = storage.retrieve_api_key("legacy_key").await.expect("The legacy key should be read");
+        assert_eq!(api_key.schema_version, CURRENT_SCHEMA_VERSION);
+
+        let stored = collection.find_one(bson::doc! { "key": "legacy_key" }).await.unwrap().unwrap();
+        assert_eq!(stored.get_i64("schema_version").ok(), Some(CURRENT_SCHEMA_VERSION as i64));
+
+        let _ = collection.delete_many(bson::doc! { "key": "legacy_key" }).await;
+    }
+
+    #[tokio::test]
+    async fn hash_map_storage_passes_the_storage_conformance_suite() {
+        check_api_key_storage(HashMapStorage::new()).await;
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn sqlite_storage_passes_the_storage_conformance_suite() {
+        check_api_key_storage(SqliteStorage::new(":memory:").await.expect("Failed to create SqliteStorage")).await;
+    }
+
+    #[tokio::test]
+    async fn cached_storage_passes_the_storage_conformance_suite() {
+        check_api_key_storage(CachedStorage::new(HashMapStorage::new(), CacheOptions::default())).await;
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn tiered_storage_passes_the_storage_conformance_suite() {
+        let durable = SqliteStorage::new(":memory:").await.expect("Failed to create SqliteStorage");
+
+        check_api_key_storage(TieredStorage::new(HashMapStorage::new(), durable)).await;
+    }
+
+    #[cfg(feature = "postgres")]
+    #[tokio::test]
+    #[ignore = "requires a running Postgres, see docker-compose.yml"]
+    async fn postgres_storage_passes_the_storage_conformance_suite() {
+        check_api_key_storage(get_postgres_storage().await).await;
+    }
+
+    #[cfg(feature = "redis")]
+    #[tokio::test]
+    #[ignore = "requires a running Redis, see docker-compose.yml"]
+    async fn redis_storage_passes_the_storage_conformance_suite() {
+        dotenv::dotenv().ok();
+        let uri = std::env::var("REDIS_URI").expect("REDIS_URI must be set");
+
+        let storage = RedisStorage::new(&uri).await.expect("Failed to create RedisStorage");
+
+        check_api_key_storage(storage.with_keyspace(RedisKeyspace::new("apikeys-test"))).await;
+    }
+
+    #[cfg(feature = "mongodb")]
+    #[tokio::test]
+    #[ignore = "requires a running MongoDB, see docker-compose.yml"]
+    async fn mongodb_storage_passes_the_storage_conformance_suite() {
+        dotenv::dotenv().ok();
+        let uri = std::env::var("MONGODB_URI").expect("MONGODB_URI must be set");
+        let db_name = std::env::var("MONGODB_DB_NAME").expect("MONGODB_DB_NAME must be set");
+
+        let storage = MongoDBStorage::new(&uri, &db_name, None).await.expect("Failed to create MongoDBStorage");
+
+        check_api_key_storage(storage).await;
+    }
+
+    #[tokio::test]
+    async fn memory_limiter_passes_the_limiter_conformance_suite() {
+        check_api_key_limiter(|clock| async move { MemoryLimiter::new().with_clock(clock) }).await;
+    }
+
+    #[tokio::test]
+    async fn limiter_chain_passes_the_limiter_conformance_suite() {
+        check_api_key_limiter(|clock| async move {
+            LimiterChain::new()
+                .with(MemoryLimiter::new().with_clock(clock.clone()))
+                .with(MemoryLimiter::new().with_clock(clock))
+        })
+        .await;
+    }
+
+    #[cfg(feature = "redis")]
+    #[tokio::test]
+    #[ignore = "requires a running Redis, see docker-compose.yml"]
+    async fn redis_limiter_passes_the_limiter_conformance_suite() {
+        dotenv::dotenv().ok();
+        let uri = std::env::var("REDIS_URI").expect("REDIS_URI must be set");
+
+        let connection =
+            RedisConnection::connect(&uri, RedisTimeouts::default()).await.expect("Failed to connect to redis");
+
+        check_api_key_limiter(|clock| {
+            let connection = connection.clone();
+            async move {
+                RedisLimiter::from_connection(connection)
+                    .with_keyspace(RedisKeyspace::new("apikeys-test"))
+                    .with_clock(clock)
+            }
+        })
+        .await;
+    }
 }
@@ -0,0 +1,141 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::{
+    clock::Clock,
+    errors::ApiKeyLimiterError,
+    traits::ApiKeyLimiter,
+    types::{ApiKey, Lease, RateLimitStatus},
+};
+
+/// Combines several limiters into one, e.g. a per-minute `RedisLimiter`, a
+/// `MemoryConcurrencyLimiter` and a service-wide cap.
+///
+/// A key is only accepted when every limiter accepts it. Limiters run in the order they were
+/// added, and when one of them rejects the key the units already consumed by the previous ones are
+/// refunded and their in-flight slots released.
+#[derive(Clone, Default)]
+pub struct LimiterChain {
+    limiters: Vec<Arc<dyn ApiKeyLimiter + Send + Sync>>,
+}
+
+impl LimiterChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with<L>(mut self, limiter: L) -> Self
+    where
+        L: ApiKeyLimiter + Send + Sync + 'static,
+    {
+        self.limiters.push(Arc::new(limiter));
+        self
+    }
+
+    async fn roll_back(limiters: &[Arc<dyn ApiKeyLimiter + Send + Sync>], api_key: &ApiKey, lease: &Lease) {
+        for limiter in limiters.iter().rev() {
+            if let Err(e) = limiter.refund_key(api_key, lease, 1).await {
+                tracing::error!("Unable to refund a key rejected by the limiter chain: {e:?}");
+            }
+
+            if let Err(e) = limiter.release_key(api_key, lease).await {
+                tracing::error!("Unable to release a key rejected by the limiter chain: {e:?}");
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl ApiKeyLimiter for LimiterChain {
+    /// Acquires the key from every limiter and releases it straight away, so that a rejection can be
+    /// rolled back while no in-flight slot is kept.
+    async fn use_key(&self, api_key: &ApiKey) -> Result<(), ApiKeyLimiterError> {
+        let lease = Lease::new();
+
+        self.acquire_key(api_key, &lease).await?;
+
+        for limiter in self.limiters.iter() {
+            if let Err(e) = limiter.release_key(api_key, &lease).await {
+                tracing::error!("Unable to release a key used through the limiter chain: {e:?}");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Every limiter of the chain is given the same lease.
+    async fn acquire_key(&self, api_key: &ApiKey, lease: &Lease) -> Result<(), ApiKeyLimiterError> {
+        for (index, limiter) in self.limiters.iter().enumerate() {
+            if let Err(e) = limiter.acquire_key(api_key, lease).await {
+                Self::roll_back(&self.limiters[..index], api_key, lease).await;
+
+                return Err(e);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn charge_key(&self, api_key: &ApiKey, lease: &Lease, units: u32) -> Result<(), ApiKeyLimiterError> {
+        let mut result = Ok(());
+
+        for limiter in self.limiters.iter() {
+            if let Err(e) = limiter.charge_key(api_key, lease, units).await {
+                result = result.and(Err(e));
+            }
+        }
+
+        result
+    }
+
+    async fn refund_key(&self, api_key: &ApiKey, lease: &Lease, units: u32) -> Result<(), ApiKeyLimiterError> {
+        let mut result = Ok(());
+
+        for limiter in self.limiters.iter() {
+            if let Err(e) = limiter.refund_key(api_key, lease, units).await {
+                result = result.and(Err(e));
+            }
+        }
+
+        result
+    }
+
+    async fn release_key(&self, api_key: &ApiKey, lease: &Lease) -> Result<(), ApiKeyLimiterError> {
+        let mut result = Ok(());
+
+        for limiter in self.limiters.iter() {
+            if let Err(e) = limiter.release_key(api_key, lease).await {
+                result = result.and(Err(e));
+            }
+        }
+
+        result
+    }
+
+    /// The status of the limiter with the fewest remaining units, which is the one that will reject
+    /// the key first.
+    async fn rate_limit_status(&self, api_key: &ApiKey) -> Result<Option<RateLimitStatus>, ApiKeyLimiterError> {
+        let mut most_restrictive: Option<RateLimitStatus> = None;
+
+        for limiter in self.limiters.iter() {
+            if let Some(status) = limiter.rate_limit_status(api_key).await? {
+                if most_restrictive.as_ref().is_none_or(|current| status.remaining < current.remaining) {
+                    most_restrictive = Some(status);
+                }
+            }
+        }
+
+        Ok(most_restrictive)
+    }
+
+    /// Limiters shared with a clone of the chain made before this call keep their own clock.
+    fn use_clock(&mut self, clock: Arc<dyn Clock>) {
+        for limiter in self.limiters.iter_mut() {
+            match Arc::get_mut(limiter) {
+                Some(limiter) => limiter.use_clock(clock.clone()),
+                None => tracing::warn!("A limiter of the chain is shared and keeps its own clock"),
+            }
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+
+use crate::{
+    errors::ApiKeyLimiterError,
+    traits::ApiKeyLimiter,
+    types::{ApiKey, ApiKeyLimit, Lease},
+};
+
+/// Enforces `max_concurrent_requests` for a single process.
+#[derive(Clone, Default)]
+pub struct MemoryConcurrencyLimiter {
+    in_flight: Arc<Mutex<HashMap<String, u32>>>,
+}
+
+impl MemoryConcurrencyLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn in_flight(&self, api_key: &ApiKey) -> u32 {
+        match self.in_flight.lock() {
+            Ok(in_flight) => in_flight.get(&api_key.key).copied().unwrap_or(0),
+            Err(_) => 0,
+        }
+    }
+}
+
+#[async_trait]
+impl ApiKeyLimiter for MemoryConcurrencyLimiter {
+    /// Only checks that a slot is free.
+    async fn use_key(&self, api_key: &ApiKey) -> Result<(), ApiKeyLimiterError> {
+        if let ApiKeyLimit::Limited(max_concurrent_requests) = api_key.limits.max_concurrent_requests {
+            if self.in_flight(api_key) >= max_concurrent_requests {
+                return Err(ApiKeyLimiterError::ConcurrencyLimitExceeded);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn acquire_key(&self, api_key: &ApiKey, _lease: &Lease) -> Result<(), ApiKeyLimiterError> {
+        match api_key.limits.max_concurrent_requests {
+            ApiKeyLimit::Limited(max_concurrent_requests) => {
+                let mut in_flight = self.in_flight.lock().map_err(|e| ApiKeyLimiterError::Other(e.to_string().into()))?;
+
+                let count = in_flight.entry(api_key.key.clone()).or_insert(0);
+
+                if *count >= max_concurrent_requests {
+                    return Err(ApiKeyLimiterError::ConcurrencyLimitExceeded);
+                }
+
+                *count += 1;
+            }
+            ApiKeyLimit::Unlimited => {}
+        }
+
+        Ok(())
+    }
+
+    /// Slots of a key are counted rather than tracked per request, so any lease frees one.
+    async fn release_key(&self, api_key: &ApiKey, _lease: &Lease) -> Result<(), ApiKeyLimiterError> {
+        match api_key.limits.max_concurrent_requests {
+            ApiKeyLimit::Limited(_) => {
+                let mut in_flight = self.in_flight.lock().map_err(|e| ApiKeyLimiterError::Other(e.to_string().into()))?;
+
+                if let Some(count) = in_flight.get_mut(&api_key.key) {
+                    *count = count.saturating_sub(1);
+
+                    if *count == 0 {
+                        in_flight.remove(&api_key.key);
+                    }
+                }
+            }
+            ApiKeyLimit::Unlimited => {}
+        }
+
+        Ok(())
+    }
+}
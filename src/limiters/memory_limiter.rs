@@ -0,0 +1,181 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+
+use crate::{
+    clock::{system_clock, Clock},
+    errors::ApiKeyLimiterError,
+    traits::ApiKeyLimiter,
+    types::{ApiKey, ApiKeyLimit, Lease, RateLimitStatus},
+};
+
+const WINDOW: Duration = Duration::minutes(1);
+
+struct Window {
+    started_at: DateTime<Utc>,
+    used: u32,
+    /// Units charged to each lease during the window, which is all a refund can give back.
+    charged: HashMap<String, u32>,
+}
+
+impl Window {
+    fn new(started_at: DateTime<Utc>) -> Self {
+        Self { started_at, used: 0, charged: HashMap::new() }
+    }
+}
+
+/// Enforces `max_reads_per_minute` for a single process using fixed one minute windows.
+///
+/// Limits can be divided by the number of nodes sharing the traffic with `with_limit_divisor`,
+/// which makes it usable as a local fallback when a shared limiter is unavailable.
+///
+/// A refund only gives back what the request of its lease was charged in the current window, so
+/// refunds made once that window has ended are dropped.
+#[derive(Clone)]
+pub struct MemoryLimiter {
+    windows: Arc<Mutex<HashMap<String, Window>>>,
+    limit_divisor: u32,
+    clock: Arc<dyn Clock>,
+}
+
+impl Default for MemoryLimiter {
+    fn default() -> Self {
+        Self { windows: Arc::default(), limit_divisor: 1, clock: system_clock() }
+    }
+}
+
+impl MemoryLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_limit_divisor(mut self, limit_divisor: u32) -> Self {
+        self.limit_divisor = limit_divisor.max(1);
+        self
+    }
+
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    fn effective_limit(&self, limit: u32) -> u32 {
+        (limit / self.limit_divisor).max(1)
+    }
+
+    /// Adds `units` to the current window of the key and returns whether it stays within `limit`.
+    /// Units within the limit are recorded against `lease`, if any, so that they can be refunded.
+    fn add_usage(
+        &self,
+        api_key: &ApiKey,
+        lease: Option<&Lease>,
+        units: u32,
+        limit: u32,
+    ) -> Result<bool, ApiKeyLimiterError> {
+        let mut windows = self.windows.lock().map_err(|e| ApiKeyLimiterError::Other(e.to_string().into()))?;
+
+        let now = self.clock.now();
+        let window = windows.entry(api_key.key.clone()).or_insert_with(|| Window::new(now));
+
+        if now - window.started_at >= WINDOW {
+            *window = Window::new(now);
+        }
+
+        window.used = window.used.saturating_add(units);
+
+        if window.used > limit {
+            return Ok(false);
+        }
+
+        if let Some(lease) = lease {
+            let charged = window.charged.entry(lease.id().to_string()).or_default();
+            *charged = charged.saturating_add(units);
+        }
+
+        Ok(true)
+    }
+}
+
+#[async_trait]
+impl ApiKeyLimiter for MemoryLimiter {
+    async fn use_key(&self, api_key: &ApiKey) -> Result<(), ApiKeyLimiterError> {
+        match api_key.limits.max_reads_per_minute {
+            ApiKeyLimit::Limited(max_reads_per_minute) => {
+                if !self.add_usage(api_key, None, 1, self.effective_limit(max_reads_per_minute))? {
+                    return Err(ApiKeyLimiterError::RateLimitExceeded);
+                }
+            }
+            ApiKeyLimit::Unlimited => {}
+        }
+
+        Ok(())
+    }
+
+    async fn acquire_key(&self, api_key: &ApiKey, lease: &Lease) -> Result<(), ApiKeyLimiterError> {
+        match api_key.limits.max_reads_per_minute {
+            ApiKeyLimit::Limited(max_reads_per_minute) => {
+                if !self.add_usage(api_key, Some(lease), 1, self.effective_limit(max_reads_per_minute))? {
+                    return Err(ApiKeyLimiterError::RateLimitExceeded);
+                }
+            }
+            ApiKeyLimit::Unlimited => {}
+        }
+
+        Ok(())
+    }
+
+    async fn charge_key(&self, api_key: &ApiKey, lease: &Lease, units: u32) -> Result<(), ApiKeyLimiterError> {
+        match api_key.limits.max_reads_per_minute {
+            ApiKeyLimit::Limited(_) => {
+                self.add_usage(api_key, Some(lease), units, u32::MAX)?;
+            }
+            ApiKeyLimit::Unlimited => {}
+        }
+
+        Ok(())
+    }
+
+    async fn refund_key(&self, api_key: &ApiKey, lease: &Lease, units: u32) -> Result<(), ApiKeyLimiterError> {
+        let mut windows = self.windows.lock().map_err(|e| ApiKeyLimiterError::Other(e.to_string().into()))?;
+
+        let now = self.clock.now();
+        let Some(window) = windows.get_mut(&api_key.key).filter(|window| now - window.started_at < WINDOW) else {
+            return Ok(());
+        };
+
+        if let Some(charged) = window.charged.get_mut(lease.id()) {
+            let refund = units.min(*charged).min(window.used);
+            *charged -= refund;
+            window.used -= refund;
+        }
+
+        Ok(())
+    }
+
+    async fn rate_limit_status(&self, api_key: &ApiKey) -> Result<Option<RateLimitStatus>, ApiKeyLimiterError> {
+        let ApiKeyLimit::Limited(max_reads_per_minute) = api_key.limits.max_reads_per_minute else {
+            return Ok(None);
+        };
+
+        let windows = self.windows.lock().map_err(|e| ApiKeyLimiterError::Other(e.to_string().into()))?;
+
+        let now = self.clock.now();
+        let limit = self.effective_limit(max_reads_per_minute);
+
+        // Without a current window, the next use starts a new one.
+        let (used, reset_at) = match windows.get(&api_key.key) {
+            Some(window) if now - window.started_at < WINDOW => (window.used, window.started_at + WINDOW),
+            _ => (0, now + WINDOW),
+        };
+
+        Ok(Some(RateLimitStatus { limit, remaining: limit.saturating_sub(used), reset_at }))
+    }
+
+    fn use_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+}
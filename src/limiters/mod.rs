@@ -1 +1,7 @@
+pub mod limiter_chain;
+pub mod memory_concurrency_limiter;
+pub mod memory_limiter;
+#[cfg(feature = "redis")]
+pub mod redis_concurrency_limiter;
+#[cfg(feature = "redis")]
 pub mod redis_limiter;
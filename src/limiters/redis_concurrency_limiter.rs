@@ -0,0 +1,143 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use redis::{AsyncCommands, RedisError, Script};
+
+use crate::{
+    errors::ApiKeyLimiterError,
+    redis_connection::{RedisConnection, RedisKeyspace, RedisTimeouts},
+    traits::ApiKeyLimiter,
+    types::{ApiKey, ApiKeyLimit, Lease},
+};
+
+// Leases are scored by their expiry time (taken from the Redis clock, so nodes do not need to agree
+// on the time). Expired leases left behind by crashed nodes are dropped before counting.
+const ACQUIRE_SCRIPT: &str = r"
+local time = redis.call('TIME')
+local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+redis.call('ZREMRANGEBYSCORE', KEYS[1], '-inf', now)
+if redis.call('ZCARD', KEYS[1]) >= tonumber(ARGV[1]) then
+    return 0
+end
+redis.call('ZADD', KEYS[1], now + tonumber(ARGV[2]), ARGV[3])
+redis.call('PEXPIRE', KEYS[1], ARGV[2])
+return 1
+";
+
+// Same as `ACQUIRE_SCRIPT`, without taking the slot.
+const CHECK_SCRIPT: &str = r"
+local time = redis.call('TIME')
+local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+redis.call('ZREMRANGEBYSCORE', KEYS[1], '-inf', now)
+if redis.call('ZCARD', KEYS[1]) >= tonumber(ARGV[1]) then
+    return 0
+end
+return 1
+";
+
+const DEFAULT_LEASE_DURATION: Duration = Duration::from_secs(300);
+
+/// Enforces `max_concurrent_requests` across every node sharing the same Redis.
+///
+/// Each request holds a lease that expires after `lease_duration`, so slots held by a node that
+/// crashed mid-request are eventually reclaimed. The lease duration should be longer than the
+/// slowest request you expect to serve.
+///
+/// `release_key` frees the slot of the lease given to `acquire_key`. `use_key` only checks that a
+/// slot is free.
+#[derive(Clone)]
+pub struct RedisConcurrencyLimiter {
+    connection: RedisConnection,
+    keyspace: RedisKeyspace,
+    lease_duration: Duration,
+}
+
+impl RedisConcurrencyLimiter {
+    pub async fn new(uri: &str, lease_duration: Option<Duration>) -> Result<Self, RedisError> {
+        Self::with_timeouts(uri, lease_duration, RedisTimeouts::default()).await
+    }
+
+    pub async fn with_timeouts(
+        uri: &str,
+        lease_duration: Option<Duration>,
+        timeouts: RedisTimeouts,
+    ) -> Result<Self, RedisError> {
+        let connection = RedisConnection::connect(uri, timeouts).await?;
+        Ok(Self::from_connection(connection, lease_duration))
+    }
+
+    pub fn from_connection(connection: RedisConnection, lease_duration: Option<Duration>) -> Self {
+        Self {
+            connection,
+            keyspace: RedisKeyspace::default(),
+            lease_duration: lease_duration.unwrap_or(DEFAULT_LEASE_DURATION),
+        }
+    }
+
+    pub fn with_keyspace(mut self, keyspace: RedisKeyspace) -> Self {
+        self.keyspace = keyspace;
+        self
+    }
+
+    fn in_flight_key(&self, api_key: &ApiKey) -> String {
+        self.keyspace.key(&api_key.key_hash(), "in_flight")
+    }
+}
+
+#[async_trait]
+impl ApiKeyLimiter for RedisConcurrencyLimiter {
+    async fn use_key(&self, api_key: &ApiKey) -> Result<(), ApiKeyLimiterError> {
+        if let ApiKeyLimit::Limited(max_concurrent_requests) = api_key.limits.max_concurrent_requests {
+            let mut connection = self.connection.clone();
+
+            let available: i32 = Script::new(CHECK_SCRIPT)
+                .key(self.in_flight_key(api_key))
+                .arg(max_concurrent_requests)
+                .invoke_async(&mut connection)
+                .await?;
+
+            if available == 0 {
+                return Err(ApiKeyLimiterError::ConcurrencyLimitExceeded);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn acquire_key(&self, api_key: &ApiKey, lease: &Lease) -> Result<(), ApiKeyLimiterError> {
+        match api_key.limits.max_concurrent_requests {
+            ApiKeyLimit::Limited(max_concurrent_requests) => {
+                let mut connection = self.connection.clone();
+
+                let acquired: i32 = Script::new(ACQUIRE_SCRIPT)
+                    .key(self.in_flight_key(api_key))
+                    .arg(max_concurrent_requests)
+                    .arg(self.lease_duration.as_millis() as u64)
+                    .arg(lease.id())
+                    .invoke_async(&mut connection)
+                    .await?;
+
+                if acquired == 0 {
+                    return Err(ApiKeyLimiterError::ConcurrencyLimitExceeded);
+                }
+            }
+            ApiKeyLimit::Unlimited => {}
+        }
+
+        Ok(())
+    }
+
+    async fn release_key(&self, api_key: &ApiKey, lease: &Lease) -> Result<(), ApiKeyLimiterError> {
+        match api_key.limits.max_concurrent_requests {
+            ApiKeyLimit::Limited(_) => {
+                let mut connection = self.connection.clone();
+
+                // Removes nothing if the lease already expired, its slot was reclaimed then.
+                let _: i32 = connection.zrem(self.in_flight_key(api_key), lease.id()).await?;
+            }
+            ApiKeyLimit::Unlimited => {}
+        }
+
+        Ok(())
+    }
+}
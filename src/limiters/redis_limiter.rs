@@ -1,22 +1,157 @@
+use std::sync::Arc;
+
 use async_trait::async_trait;
-use redis::{AsyncCommands, Client, RedisError};
+use chrono::{DateTime, Utc};
+use redis::{AsyncCommands, RedisError, Script};
 
 use crate::{
+    clock::{system_clock, Clock},
     errors::ApiKeyLimiterError,
+    redis_connection::{RedisConnection, RedisKeyspace, RedisTimeouts},
     traits::ApiKeyLimiter,
-    types::{ApiKey, ApiKeyLimit},
+    types::{ApiKey, ApiKeyLimit, Lease, RateLimitStatus},
 };
 
+const WINDOW_SECONDS: i64 = 60;
+// Counters outlive their window a little, so that nodes whose clocks are slightly behind still
+// count against the same window.
+const COUNTER_TTL_SECONDS: i64 = 2 * WINDOW_SECONDS;
+
+// Gives back at most what the lease (ARGV[2]) was charged in the window of the counter, without
+// taking the counter below zero and without creating either key.
+const REFUND_SCRIPT: &str = r"
+local count = tonumber(redis.call('GET', KEYS[1]) or '0')
+local charged = tonumber(redis.call('HGET', KEYS[2], ARGV[2]) or '0')
+local refund = math.min(count, charged, tonumber(ARGV[1]))
+if refund > 0 then
+    redis.call('DECRBY', KEYS[1], refund)
+    redis.call('HINCRBY', KEYS[2], ARGV[2], -refund)
+end
+return refund
+";
+
+/// Enforces `max_reads_per_minute` across every node sharing a Redis, using fixed one minute
+/// windows aligned on the clock (`with_clock`) of the nodes.
+///
+/// The units charged to each lease are recorded with the counter of their window, and a refund only
+/// gives back what its lease was charged in the current window. Refunds made once that window has
+/// ended are dropped, so they never raise the limit of the next window.
 #[derive(Clone)]
 pub struct RedisLimiter {
-    redis_client: Client,
+    connection: RedisConnection,
+    keyspace: RedisKeyspace,
+    migrate_legacy_counters: bool,
+    clock: Arc<dyn Clock>,
 }
 
 impl RedisLimiter {
     pub async fn new(uri: &str) -> Result<Self, RedisError> {
-        tracing::debug!("Creating redis client from uri: {}", uri);
-        let redis_client = Client::open(uri)?;
-        Ok(Self { redis_client })
+        Self::with_timeouts(uri, RedisTimeouts::default()).await
+    }
+
+    pub async fn with_timeouts(uri: &str, timeouts: RedisTimeouts) -> Result<Self, RedisError> {
+        let connection = RedisConnection::connect(uri, timeouts).await?;
+        Ok(Self::from_connection(connection))
+    }
+
+    pub fn from_connection(connection: RedisConnection) -> Self {
+        Self {
+            connection,
+            keyspace: RedisKeyspace::default(),
+            migrate_legacy_counters: false,
+            clock: system_clock(),
+        }
+    }
+
+    pub fn with_keyspace(mut self, keyspace: RedisKeyspace) -> Self {
+        self.keyspace = keyspace;
+        self
+    }
+
+    /// Carries over counters created by earlier versions, which were named after the plaintext key
+    /// (`{key}_read_count`), the first time a key is used after upgrading. Those counters expire
+    /// within a minute, so this only needs to stay enabled while a deployment is rolling out.
+    pub fn with_legacy_counter_migration(mut self, migrate_legacy_counters: bool) -> Self {
+        self.migrate_legacy_counters = migrate_legacy_counters;
+        self
+    }
+
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Start of the current window, in seconds since the epoch.
+    fn window_start(&self) -> i64 {
+        let now = self.clock.now().timestamp();
+        now - now.rem_euclid(WINDOW_SECONDS)
+    }
+
+    fn read_count_key(&self, api_key: &ApiKey, window_start: i64) -> String {
+        self.keyspace.key(&api_key.key_hash(), &format!("read_count:{window_start}"))
+    }
+
+    fn charges_key(&self, api_key: &ApiKey, window_start: i64) -> String {
+        self.keyspace.key(&api_key.key_hash(), &format!("charges:{window_start}"))
+    }
+
+    fn legacy_read_count_key(api_key: &ApiKey) -> String {
+        format!("{}_read_count", api_key.key)
+    }
+
+    async fn increment_read_count(
+        &self,
+        api_key: &ApiKey,
+        window_start: i64,
+        units: u32,
+    ) -> Result<i64, ApiKeyLimiterError> {
+        let mut connection = self.connection.clone();
+        let key = self.read_count_key(api_key, window_start);
+
+        // Only the first use of the window creates the counter, concurrent uses must not reset it.
+        let created: bool = redis::cmd("SET")
+            .arg(&key)
+            .arg(0)
+            .arg("NX")
+            .arg("EX")
+            .arg(COUNTER_TTL_SECONDS)
+            .query_async::<Option<String>>(&mut connection)
+            .await?
+            .is_some();
+
+        if created && self.migrate_legacy_counters {
+            let legacy_count: Option<i64> = connection.get_del(Self::legacy_read_count_key(api_key)).await?;
+
+            if let Some(legacy_count) = legacy_count {
+                let _: i64 = connection.incr(&key, legacy_count).await?;
+            }
+        }
+
+        let result: i64 = connection.incr(&key, units).await?;
+
+        Ok(result)
+    }
+
+    async fn record_charge(
+        &self,
+        api_key: &ApiKey,
+        window_start: i64,
+        lease: &Lease,
+        units: u32,
+    ) -> Result<(), ApiKeyLimiterError> {
+        let mut connection = self.connection.clone();
+        let key = self.charges_key(api_key, window_start);
+
+        let _: () = redis::pipe()
+            .atomic()
+            .hincr(&key, lease.id(), units)
+            .ignore()
+            .expire(&key, COUNTER_TTL_SECONDS)
+            .ignore()
+            .query_async(&mut connection)
+            .await?;
+
+        Ok(())
     }
 }
 
@@ -25,34 +160,100 @@ impl ApiKeyLimiter for RedisLimiter {
     async fn use_key(&self, api_key: &ApiKey) -> Result<(), ApiKeyLimiterError> {
         match api_key.limits.max_reads_per_minute {
             ApiKeyLimit::Limited(max_reads_per_minute) => {
-                let mut connection = self.redis_client.get_multiplexed_async_connection().await?;
-
-                let key = format!("{}_read_count", api_key.key);
+                let result = self.increment_read_count(api_key, self.window_start(), 1).await?;
 
-                match connection.exists(&key).await? {
-                    true => {}
-                    false => {
-                        let _: Result<String, RedisError> = connection.set(&key, 0).await;
-
-                        let _: Result<String, RedisError> = connection.expire(&key, 60).await;
-                    }
+                if result > i64::from(max_reads_per_minute) {
+                    return Err(ApiKeyLimiterError::RateLimitExceeded);
                 }
+            }
+            ApiKeyLimit::Unlimited => {}
+        }
+
+        Ok(())
+    }
 
-                let result: i32 = connection.incr(&key, 1).await?;
+    async fn acquire_key(&self, api_key: &ApiKey, lease: &Lease) -> Result<(), ApiKeyLimiterError> {
+        match api_key.limits.max_reads_per_minute {
+            ApiKeyLimit::Limited(max_reads_per_minute) => {
+                let window_start = self.window_start();
+                let result = self.increment_read_count(api_key, window_start, 1).await?;
 
-                if result > max_reads_per_minute as i32 {
+                if result > i64::from(max_reads_per_minute) {
                     return Err(ApiKeyLimiterError::RateLimitExceeded);
                 }
+
+                self.record_charge(api_key, window_start, lease, 1).await?;
+            }
+            ApiKeyLimit::Unlimited => {}
+        }
+
+        Ok(())
+    }
+
+    async fn charge_key(&self, api_key: &ApiKey, lease: &Lease, units: u32) -> Result<(), ApiKeyLimiterError> {
+        if units == 0 {
+            return Ok(());
+        }
+
+        match api_key.limits.max_reads_per_minute {
+            ApiKeyLimit::Limited(_) => {
+                let window_start = self.window_start();
+                self.increment_read_count(api_key, window_start, units).await?;
+                self.record_charge(api_key, window_start, lease, units).await?;
             }
             ApiKeyLimit::Unlimited => {}
         }
 
         Ok(())
     }
+
+    async fn refund_key(&self, api_key: &ApiKey, lease: &Lease, units: u32) -> Result<(), ApiKeyLimiterError> {
+        if units == 0 {
+            return Ok(());
+        }
+
+        match api_key.limits.max_reads_per_minute {
+            ApiKeyLimit::Limited(_) => {
+                let mut connection = self.connection.clone();
+                let window_start = self.window_start();
+
+                let _: i64 = Script::new(REFUND_SCRIPT)
+                    .key(self.read_count_key(api_key, window_start))
+                    .key(self.charges_key(api_key, window_start))
+                    .arg(units)
+                    .arg(lease.id())
+                    .invoke_async(&mut connection)
+                    .await?;
+            }
+            ApiKeyLimit::Unlimited => {}
+        }
+
+        Ok(())
+    }
+
+    async fn rate_limit_status(&self, api_key: &ApiKey) -> Result<Option<RateLimitStatus>, ApiKeyLimiterError> {
+        let ApiKeyLimit::Limited(limit) = api_key.limits.max_reads_per_minute else {
+            return Ok(None);
+        };
+
+        let mut connection = self.connection.clone();
+
+        let window_start = self.window_start();
+        let used: Option<i64> = connection.get(self.read_count_key(api_key, window_start)).await?;
+
+        let remaining = (limit as i64 - used.unwrap_or(0).max(0)).max(0) as u32;
+        let reset_at = DateTime::<Utc>::from_timestamp(window_start + WINDOW_SECONDS, 0).unwrap_or_default();
+
+        Ok(Some(RateLimitStatus { limit, remaining, reset_at }))
+    }
+
+    fn use_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
 }
 
 impl From<RedisError> for ApiKeyLimiterError {
     fn from(error: RedisError) -> Self {
-        ApiKeyLimiterError::Other(error.to_string())
+        ApiKeyLimiterError::Other(error.into())
     }
 }
@@ -0,0 +1,130 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use tracing::{info, warn};
+
+use crate::{clock::Clock, traits::ApiKeyLimiter};
+
+/// What `KeyManager` does when its limiter backend cannot be reached.
+#[derive(Clone, Default)]
+pub enum FailurePolicy {
+    /// Reject every request until the limiter is back.
+    #[default]
+    FailClosed,
+    /// Accept every request without rate limiting until the limiter is back.
+    FailOpen,
+    /// Rate limit with a local limiter, usually a `MemoryLimiter` with reduced limits.
+    Fallback(Arc<dyn ApiKeyLimiter + Send + Sync>),
+}
+
+impl FailurePolicy {
+    pub fn fallback<L>(limiter: L) -> Self
+    where
+        L: ApiKeyLimiter + Send + Sync + 'static,
+    {
+        FailurePolicy::Fallback(Arc::new(limiter))
+    }
+
+    /// Hands `clock` to the fallback limiter, unless it is shared with a clone of this policy.
+    pub(crate) fn use_clock(&mut self, clock: Arc<dyn Clock>) {
+        if let FailurePolicy::Fallback(limiter) = self {
+            match Arc::get_mut(limiter) {
+                Some(limiter) => limiter.use_clock(clock),
+                None => warn!("The fallback limiter is shared and keeps its own clock"),
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum CircuitState {
+    Closed { failures: u32 },
+    /// Until `Clock::monotonic` reaches `until`.
+    Open { until: Duration },
+    /// A single request probes the backend. `probe_started_at` lets another one through if its outcome
+    /// is never recorded, e.g. because the request was dropped.
+    HalfOpen { probe_started_at: Duration },
+}
+
+/// Stops calling a limiter backend after `failure_threshold` consecutive failures.
+///
+/// While open, `KeyManager` applies its `FailurePolicy` straight away. Once `open_duration` has
+/// elapsed the breaker lets a single request through to probe the backend, the others still getting
+/// the `FailurePolicy`, and closes if it succeeds. Time is read from the monotonic clock of the
+/// `KeyManager` (see `KeyManager::with_clock`).
+#[derive(Clone)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    open_duration: Duration,
+    state: Arc<Mutex<CircuitState>>,
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self::new(5, Duration::from_secs(30))
+    }
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, open_duration: Duration) -> Self {
+        Self {
+            failure_threshold: failure_threshold.max(1),
+            open_duration,
+            state: Arc::new(Mutex::new(CircuitState::Closed { failures: 0 })),
+        }
+    }
+
+    /// `now` is read from `Clock::monotonic`, like the times given to `record_failure`.
+    pub(crate) fn allows_request(&self, now: Duration) -> bool {
+        let Ok(mut state) = self.state.lock() else {
+            return true;
+        };
+
+        match *state {
+            CircuitState::Closed { .. } => true,
+            CircuitState::Open { until } if now >= until => {
+                info!("Limiter circuit breaker is half-open, probing the limiter backend");
+                *state = CircuitState::HalfOpen { probe_started_at: now };
+                true
+            }
+            CircuitState::HalfOpen { probe_started_at }
+                if now >= probe_started_at.saturating_add(self.open_duration) =>
+            {
+                *state = CircuitState::HalfOpen { probe_started_at: now };
+                true
+            }
+            CircuitState::Open { .. } | CircuitState::HalfOpen { .. } => false,
+        }
+    }
+
+    pub(crate) fn record_success(&self) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+
+        if !matches!(*state, CircuitState::Closed { .. }) {
+            info!("Limiter circuit breaker closed, the limiter backend is reachable again");
+        }
+
+        *state = CircuitState::Closed { failures: 0 };
+    }
+
+    pub(crate) fn record_failure(&self, now: Duration) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+
+        match *state {
+            CircuitState::Closed { failures } if failures + 1 < self.failure_threshold => {
+                *state = CircuitState::Closed { failures: failures + 1 };
+            }
+            CircuitState::Closed { .. } | CircuitState::HalfOpen { .. } => {
+                warn!("Limiter circuit breaker opened for {:?}, the limiter backend is failing", self.open_duration);
+                *state = CircuitState::Open { until: now.saturating_add(self.open_duration) };
+            }
+            CircuitState::Open { .. } => {}
+        }
+    }
+}
@@ -1,9 +1,15 @@
+use std::{sync::Arc, time::Duration};
+
 use async_trait::async_trait;
 
+pub mod failure_policy;
+
+use self::failure_policy::{CircuitBreaker, FailurePolicy};
 use crate::{
-    errors::{ApiKeyLimiterError, ApiKeyManagerError, ApiKeyStorageError},
+    clock::{Clock, SystemClock},
+    errors::{ApiKeyLimiterError, ApiKeyManagerError, ApiKeyStorageError, BoxError},
     traits::{ApiKeyLimiter, ApiKeyManager, ApiKeyStorage},
-    types::ApiKey,
+    types::{AdmittedBy, ApiKey, Lease},
 };
 
 #[derive(Clone)]
@@ -14,6 +20,10 @@ where
 {
     storage: S,
     limiter: L,
+    failure_policy: FailurePolicy,
+    circuit_breaker: CircuitBreaker,
+    // Set by `with_clock`, the system clock is used otherwise.
+    clock: Option<Arc<dyn Clock>>,
 }
 
 impl<S, L> KeyManager<S, L>
@@ -22,7 +32,110 @@ where
     L: ApiKeyLimiter + Send + Sync,
 {
     pub fn new(storage: S, limiter: L) -> Self {
-        KeyManager { storage, limiter }
+        KeyManager {
+            storage,
+            limiter,
+            failure_policy: FailurePolicy::default(),
+            circuit_breaker: CircuitBreaker::default(),
+            clock: None,
+        }
+    }
+
+    pub fn with_failure_policy(mut self, failure_policy: FailurePolicy) -> Self {
+        self.failure_policy = failure_policy;
+
+        if let Some(clock) = self.clock.clone() {
+            self.failure_policy.use_clock(clock);
+        }
+
+        self
+    }
+
+    pub fn with_circuit_breaker(mut self, circuit_breaker: CircuitBreaker) -> Self {
+        self.circuit_breaker = circuit_breaker;
+        self
+    }
+
+    /// Clock the circuit breaker measures `open_duration` with. It is handed to the storage, the
+    /// limiter and the fallback limiter of the failure policy as well (see `use_clock`).
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        let clock: Arc<dyn Clock> = Arc::new(clock);
+
+        self.storage.use_clock(clock.clone());
+        self.limiter.use_clock(clock.clone());
+        self.failure_policy.use_clock(clock.clone());
+        self.clock = Some(clock);
+
+        self
+    }
+
+    fn monotonic(&self) -> Duration {
+        match &self.clock {
+            Some(clock) => clock.monotonic(),
+            None => SystemClock.monotonic(),
+        }
+    }
+
+    fn allows_request(&self) -> bool {
+        self.circuit_breaker.allows_request(self.monotonic())
+    }
+
+    /// Returns the limiter to use while the primary one is unavailable, if the policy has one.
+    fn fallback_limiter(&self) -> Option<&(dyn ApiKeyLimiter + Send + Sync)> {
+        match &self.failure_policy {
+            FailurePolicy::Fallback(limiter) => Some(limiter.as_ref()),
+            FailurePolicy::FailClosed | FailurePolicy::FailOpen => None,
+        }
+    }
+
+    /// Limiter that admitted the request of `lease`, which charges, refunds and releases it whatever
+    /// the state of the circuit breaker is by then. `None` if no limiter did.
+    fn limiter_of(&self, lease: &Lease) -> Option<&(dyn ApiKeyLimiter + Send + Sync)> {
+        match lease.admitted_by {
+            AdmittedBy::Limiter => Some(&self.limiter),
+            AdmittedBy::Fallback => self.fallback_limiter(),
+            AdmittedBy::Nobody => None,
+        }
+    }
+
+    /// Applies the failure policy to a use (`lease` is `None`) or an acquisition of the key.
+    async fn use_key_without_limiter(
+        &self,
+        api_key: &ApiKey,
+        lease: Option<&mut Lease>,
+        error: BoxError,
+    ) -> Result<(), ApiKeyLimiterError> {
+        let admitted_by = match &self.failure_policy {
+            FailurePolicy::FailClosed => return Err(ApiKeyLimiterError::Other(error)),
+            FailurePolicy::FailOpen => AdmittedBy::Nobody,
+            FailurePolicy::Fallback(limiter) => {
+                match &lease {
+                    Some(lease) => limiter.acquire_key(api_key, lease).await?,
+                    None => limiter.use_key(api_key).await?,
+                }
+
+                AdmittedBy::Fallback
+            }
+        };
+
+        if let Some(lease) = lease {
+            lease.admitted_by = admitted_by;
+        }
+
+        Ok(())
+    }
+
+    /// Keeps the circuit breaker up to date with the outcome of a call to the primary limiter.
+    fn observe<T>(&self, result: Result<T, ApiKeyLimiterError>) -> Result<T, ApiKeyLimiterError> {
+        match &result {
+            Err(ApiKeyLimiterError::Other(e)) => {
+                tracing::warn!("Limiter backend error: {e}");
+                self.circuit_breaker.record_failure(self.monotonic());
+            }
+            _ => self.circuit_breaker.record_success(),
+        }
+
+        result
     }
 }
 
@@ -38,10 +151,63 @@ where
         Ok(api_key)
     }
 
-    async fn use_key(&self, key: &str) -> Result<(), ApiKeyManagerError> {
+    async fn use_key(&self, key: &str) -> Result<ApiKey, ApiKeyManagerError> {
         let api_key = self.get_key(key).await?;
 
-        self.limiter.use_key(&api_key).await?;
+        if !self.allows_request() {
+            self.use_key_without_limiter(&api_key, None, "Limiter circuit breaker is open".into()).await?;
+
+            return Ok(api_key);
+        }
+
+        match self.observe(self.limiter.use_key(&api_key).await) {
+            Ok(()) => {}
+            Err(ApiKeyLimiterError::Other(e)) => self.use_key_without_limiter(&api_key, None, e).await?,
+            Err(e) => return Err(e.into()),
+        }
+
+        Ok(api_key)
+    }
+
+    async fn acquire_key(&self, key: &str) -> Result<(ApiKey, Lease), ApiKeyManagerError> {
+        let api_key = self.get_key(key).await?;
+        let mut lease = Lease::new();
+
+        if !self.allows_request() {
+            self.use_key_without_limiter(&api_key, Some(&mut lease), "Limiter circuit breaker is open".into()).await?;
+
+            return Ok((api_key, lease));
+        }
+
+        match self.observe(self.limiter.acquire_key(&api_key, &lease).await) {
+            Ok(()) => {}
+            Err(ApiKeyLimiterError::Other(e)) => self.use_key_without_limiter(&api_key, Some(&mut lease), e).await?,
+            Err(e) => return Err(e.into()),
+        }
+
+        Ok((api_key, lease))
+    }
+
+    async fn charge_key(&self, api_key: &ApiKey, lease: &Lease, units: u32) -> Result<(), ApiKeyManagerError> {
+        if let Some(limiter) = self.limiter_of(lease) {
+            limiter.charge_key(api_key, lease, units).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn refund_key(&self, api_key: &ApiKey, lease: &Lease, units: u32) -> Result<(), ApiKeyManagerError> {
+        if let Some(limiter) = self.limiter_of(lease) {
+            limiter.refund_key(api_key, lease, units).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn release_key(&self, api_key: &ApiKey, lease: &Lease) -> Result<(), ApiKeyManagerError> {
+        if let Some(limiter) = self.limiter_of(lease) {
+            limiter.release_key(api_key, lease).await?;
+        }
 
         Ok(())
     }
@@ -1,15 +1,10 @@
-use crate::types;
+use crate::{clock::Clock, testing::api_key::TestApiKey, types};
 
 pub fn get_mock_api_key(key: Option<String>) -> types::ApiKey {
-    types::ApiKey {
-        key: key.unwrap_or("test_key".to_string()),
-        limits: types::ApiKeyLimits {
-            max_reads_per_minute: types::ApiKeyLimit::Limited(100),
-            max_writes_per_minute: types::ApiKeyLimit::Limited(100),
-        },
-        restrictions: types::ApiKeyRestrictions { allowed_domains: vec!["example.com".to_string()] },
-        status: types::ApiKeyStatus::Active,
-        created_at: chrono::Utc::now(),
-        updated_at: chrono::Utc::now(),
-    }
+    TestApiKey::new(key.unwrap_or("test_key".to_string())).build()
+}
+
+/// Same as `get_mock_api_key`, created and updated at the current time of `clock`.
+pub fn get_mock_api_key_at(key: Option<String>, clock: &dyn Clock) -> types::ApiKey {
+    TestApiKey::new(key.unwrap_or("test_key".to_string())).with_created_at(clock.now()).build()
 }
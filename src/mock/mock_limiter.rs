@@ -0,0 +1,32 @@
+use async_trait::async_trait;
+
+use crate::{errors::ApiKeyLimiterError, testing::limiter::MockLimiter, traits::ApiKeyLimiter, types::ApiKey};
+
+/// Accepts every key and counts the units used.
+pub type CountingLimiter = MockLimiter;
+
+/// Fails every use like a limiter whose backend is down.
+#[derive(Clone)]
+pub struct FailingLimiter {
+    inner: MockLimiter,
+}
+
+impl FailingLimiter {
+    pub fn new() -> Self {
+        let inner = MockLimiter::new();
+        inner.force_error("Connection refused");
+
+        Self { inner }
+    }
+
+    pub fn calls(&self) -> usize {
+        self.inner.uses()
+    }
+}
+
+#[async_trait]
+impl ApiKeyLimiter for FailingLimiter {
+    async fn use_key(&self, api_key: &ApiKey) -> Result<(), ApiKeyLimiterError> {
+        self.inner.use_key(api_key).await
+    }
+}
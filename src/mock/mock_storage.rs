@@ -0,0 +1,4 @@
+use crate::testing::storage::RecordingStorage;
+
+/// In-memory storage which counts retrievals and can be taken down.
+pub type CountingStorage = RecordingStorage;
@@ -1 +1,3 @@
 pub mod mock_api_key;
+pub mod mock_limiter;
+pub mod mock_storage;
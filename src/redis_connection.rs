@@ -0,0 +1,117 @@
+use std::time::Duration;
+
+use redis::{
+    aio::{ConnectionLike, ConnectionManager, ConnectionManagerConfig},
+    cluster::ClusterClient,
+    cluster_async::ClusterConnection,
+    Client, Cmd, Pipeline, RedisError, RedisFuture, Value,
+};
+
+/// Timeouts applied to the shared Redis connection.
+#[derive(Debug, Clone, Copy)]
+pub struct RedisTimeouts {
+    /// How long a single attempt to (re)connect to Redis may take.
+    pub connection_timeout: Duration,
+    /// How long a command may wait for its response before failing.
+    pub response_timeout: Duration,
+}
+
+impl Default for RedisTimeouts {
+    fn default() -> Self {
+        Self { connection_timeout: Duration::from_secs(1), response_timeout: Duration::from_secs(1) }
+    }
+}
+
+/// Connection shared by every clone of its owner, to either a single Redis node or a Redis Cluster.
+/// Both variants transparently reconnect when a node goes away.
+#[derive(Clone)]
+pub enum RedisConnection {
+    Single(ConnectionManager),
+    Cluster(ClusterConnection),
+}
+
+impl RedisConnection {
+    pub async fn connect(uri: &str, timeouts: RedisTimeouts) -> Result<Self, RedisError> {
+        tracing::debug!("Creating redis client from uri: {}", uri);
+        let redis_client = Client::open(uri)?;
+
+        let config = ConnectionManagerConfig::new()
+            .set_connection_timeout(timeouts.connection_timeout)
+            .set_response_timeout(timeouts.response_timeout);
+
+        Ok(RedisConnection::Single(ConnectionManager::new_with_config(redis_client, config).await?))
+    }
+
+    pub async fn connect_cluster(nodes: &[&str], timeouts: RedisTimeouts) -> Result<Self, RedisError> {
+        tracing::debug!("Creating redis cluster client from nodes: {:?}", nodes);
+        let cluster_client = ClusterClient::builder(nodes.to_vec())
+            .connection_timeout(timeouts.connection_timeout)
+            .response_timeout(timeouts.response_timeout)
+            .build()?;
+
+        Ok(RedisConnection::Cluster(cluster_client.get_async_connection().await?))
+    }
+}
+
+impl ConnectionLike for RedisConnection {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+        match self {
+            RedisConnection::Single(connection) => connection.req_packed_command(cmd),
+            RedisConnection::Cluster(connection) => connection.req_packed_command(cmd),
+        }
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        cmd: &'a Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> RedisFuture<'a, Vec<Value>> {
+        match self {
+            RedisConnection::Single(connection) => connection.req_packed_commands(cmd, offset, count),
+            RedisConnection::Cluster(connection) => connection.req_packed_commands(cmd, offset, count),
+        }
+    }
+
+    fn get_db(&self) -> i64 {
+        match self {
+            RedisConnection::Single(connection) => connection.get_db(),
+            RedisConnection::Cluster(connection) => connection.get_db(),
+        }
+    }
+}
+
+/// Builds the Redis keys used for an API key.
+///
+/// Keys look like `{namespace}:{{id}}:{suffix}`. The id is wrapped in a hash tag so that every
+/// key belonging to the same API key lands on the same Redis Cluster slot.
+#[derive(Debug, Clone)]
+pub struct RedisKeyspace {
+    namespace: String,
+}
+
+impl Default for RedisKeyspace {
+    fn default() -> Self {
+        Self::new("apikeys")
+    }
+}
+
+impl RedisKeyspace {
+    /// Braces are removed from the namespace, as they would otherwise be taken for the hash tag.
+    pub fn new(namespace: &str) -> Self {
+        Self { namespace: namespace.replace(['{', '}'], "") }
+    }
+
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    pub fn key(&self, id: &str, suffix: &str) -> String {
+        format!("{}:{{{}}}:{}", self.namespace, id, suffix)
+    }
+
+    /// Key of a set indexing API keys by one of their attributes, e.g. `index_key("status", "Active")`.
+    pub fn index_key(&self, index: &str, value: &str) -> String {
+        format!("{}:index:{}:{}", self.namespace, index, value)
+    }
+}
@@ -0,0 +1,83 @@
+//! Versions of the shape of stored `ApiKey` records, and the upcasters converting older records to
+//! the current shape when they are read.
+//!
+//! A field added to `ApiKey` either has a `#[serde(default)]` matching what older records mean, or
+//! comes with a new version and an upcaster filling it in. Renamed or retyped fields always need a
+//! new version.
+//!
+//! | Version | Shape                                                                 |
+//! |---------|-----------------------------------------------------------------------|
+//! | 0       | No `schema_version`, `owner` or `limits.max_concurrent_requests`      |
+//! | 1       | `schema_version`, `owner` and `limits.max_concurrent_requests` added  |
+
+use serde_json::{json, Map, Value};
+
+use crate::{errors::ApiKeyStorageError, types::ApiKey};
+
+pub const CURRENT_SCHEMA_VERSION: u32 = 1;
+
+type Upcaster = fn(&mut Map<String, Value>) -> Result<(), String>;
+
+/// `UPCASTERS[n]` converts a record of version `n` to version `n + 1`.
+const UPCASTERS: [Upcaster; CURRENT_SCHEMA_VERSION as usize] = [v0_to_v1];
+
+/// A record read in the current shape.
+#[derive(Debug, Clone)]
+pub struct Upcasted {
+    pub api_key: ApiKey,
+    /// Version the record was stored with.
+    pub stored_version: u32,
+}
+
+impl Upcasted {
+    /// Whether the stored record is older than the current version, and can be rewritten.
+    pub fn is_outdated(&self) -> bool {
+        self.stored_version < CURRENT_SCHEMA_VERSION
+    }
+}
+
+/// Converts a stored record of any known version to an `ApiKey`. Records from a newer version of
+/// this crate are rejected rather than read partially.
+pub fn upcast(record: Value) -> Result<Upcasted, ApiKeyStorageError> {
+    let Value::Object(mut record) = record else {
+        return Err(invalid_record("the record is not an object".to_string()));
+    };
+
+    let stored_version = match record.get("schema_version") {
+        None | Some(Value::Null) => 0,
+        Some(version) => version
+            .as_u64()
+            .and_then(|version| u32::try_from(version).ok())
+            .ok_or_else(|| invalid_record(format!("invalid schema version {version}")))?,
+    };
+
+    if stored_version > CURRENT_SCHEMA_VERSION {
+        return Err(invalid_record(format!(
+            "schema version {stored_version} is newer than the supported version {CURRENT_SCHEMA_VERSION}"
+        )));
+    }
+
+    for (version, upcaster) in UPCASTERS.iter().enumerate().skip(stored_version as usize) {
+        upcaster(&mut record)
+            .map_err(|e| invalid_record(format!("unable to upgrade the record from version {version}: {e}")))?;
+        record.insert("schema_version".to_string(), json!(version + 1));
+    }
+
+    let api_key = serde_json::from_value(Value::Object(record))
+        .map_err(|e| ApiKeyStorageError::SerializationError(e.into()))?;
+
+    Ok(Upcasted { api_key, stored_version })
+}
+
+fn invalid_record(message: String) -> ApiKeyStorageError {
+    ApiKeyStorageError::SerializationError(message.into())
+}
+
+fn v0_to_v1(record: &mut Map<String, Value>) -> Result<(), String> {
+    let limits = record.get_mut("limits").and_then(Value::as_object_mut).ok_or("the limits are missing")?;
+    limits.entry("max_concurrent_requests").or_insert_with(|| json!("Unlimited"));
+
+    record.entry("owner").or_insert(Value::Null);
+
+    Ok(())
+}
@@ -0,0 +1,298 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+
+use crate::{
+    clock::{system_clock, Clock},
+    errors::{ApiKeyStorageError, ErrorChain},
+    invalidation::{KeyChangeEvent, KeyChangeListener},
+    traits::{ApiKeyLister, ApiKeyStorage},
+    types::{ApiKey, ApiKeyPage},
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct CacheOptions {
+    /// How long a retrieved key is served without asking the underlying storage again.
+    pub ttl: Duration,
+    /// How long after `ttl` an expired key is still served while it is refreshed in the background.
+    pub stale_ttl: Duration,
+    /// How long an unknown key is remembered as missing, so that guessing keys does not reach the
+    /// underlying storage on every request.
+    pub negative_ttl: Duration,
+    /// Maximum number of keys, found or missing, held by the cache.
+    pub max_entries: usize,
+}
+
+impl Default for CacheOptions {
+    fn default() -> Self {
+        Self {
+            ttl: Duration::from_secs(30),
+            stale_ttl: Duration::from_secs(30),
+            negative_ttl: Duration::from_secs(5),
+            max_entries: 10_000,
+        }
+    }
+}
+
+#[derive(Clone)]
+enum CachedValue {
+    Found(ApiKey),
+    Missing,
+}
+
+struct CacheEntry {
+    value: CachedValue,
+    /// `Clock::monotonic` when the value was fetched.
+    fetched_at: Duration,
+    generation: u64,
+    refreshing: bool,
+}
+
+enum Lookup {
+    Fresh(CachedValue),
+    Stale(ApiKey, bool),
+    Miss,
+}
+
+#[derive(Default)]
+struct Cache {
+    entries: HashMap<String, CacheEntry>,
+    insertion_order: VecDeque<(String, u64)>,
+    next_generation: u64,
+    // Bumped by every invalidation, so that a fetch that raced with one does not cache what it read.
+    invalidations: u64,
+}
+
+impl Cache {
+    fn lookup(&mut self, key_hash: &str, options: &CacheOptions, now: Duration) -> Lookup {
+        let Some(entry) = self.entries.get_mut(key_hash) else {
+            return Lookup::Miss;
+        };
+
+        let age = now.saturating_sub(entry.fetched_at);
+
+        match &entry.value {
+            CachedValue::Found(_) if age < options.ttl => Lookup::Fresh(entry.value.clone()),
+            CachedValue::Missing if age < options.negative_ttl => Lookup::Fresh(entry.value.clone()),
+            CachedValue::Found(api_key) if age < options.ttl + options.stale_ttl => {
+                let needs_refresh = !entry.refreshing;
+                entry.refreshing = true;
+
+                Lookup::Stale(api_key.clone(), needs_refresh)
+            }
+            _ => Lookup::Miss,
+        }
+    }
+
+    fn insert(&mut self, key_hash: String, value: CachedValue, options: &CacheOptions, now: Duration) {
+        if let Some(entry) = self.entries.get_mut(&key_hash) {
+            entry.value = value;
+            entry.fetched_at = now;
+            entry.refreshing = false;
+            return;
+        }
+
+        let generation = self.next_generation;
+        self.next_generation += 1;
+
+        self.insertion_order.push_back((key_hash.clone(), generation));
+        self.entries.insert(key_hash, CacheEntry { value, fetched_at: now, generation, refreshing: false });
+
+        // Oldest entries are evicted first. The generation tells apart an entry that was removed and
+        // inserted again from the one that was queued originally.
+        while self.entries.len() > options.max_entries {
+            let Some((key_hash, generation)) = self.insertion_order.pop_front() else {
+                break;
+            };
+
+            if self.entries.get(&key_hash).is_some_and(|entry| entry.generation == generation) {
+                self.entries.remove(&key_hash);
+            }
+        }
+
+        if self.insertion_order.len() > self.entries.len() * 2 {
+            let entries = &self.entries;
+            self.insertion_order.retain(|(key_hash, generation)| {
+                entries.get(key_hash).is_some_and(|entry| entry.generation == *generation)
+            });
+        }
+    }
+
+    fn stop_refreshing(&mut self, key_hash: &str) {
+        if let Some(entry) = self.entries.get_mut(key_hash) {
+            entry.refreshing = false;
+        }
+    }
+}
+
+/// Caches the keys retrieved from any `ApiKeyStorage`.
+///
+/// Keys are cached by hash for `CacheOptions::ttl` and then served stale for up to
+/// `CacheOptions::stale_ttl` while a background task fetches them again. Unknown keys are cached as
+/// well. Storing or deleting a key through the cache invalidates it, changes made to the underlying
+/// storage by other means can be propagated with `invalidate` or by subscribing the cache to key
+/// change events (see `invalidation`).
+#[derive(Clone)]
+pub struct CachedStorage<S>
+where
+    S: ApiKeyStorage + Clone + Send + Sync + 'static,
+{
+    inner: S,
+    options: CacheOptions,
+    cache: Arc<Mutex<Cache>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl<S> CachedStorage<S>
+where
+    S: ApiKeyStorage + Clone + Send + Sync + 'static,
+{
+    pub fn new(inner: S, options: CacheOptions) -> Self {
+        Self { inner, options, cache: Arc::default(), clock: system_clock() }
+    }
+
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    pub fn invalidate(&self, key: &str) {
+        self.invalidate_hash(&ApiKey::hash_key(key));
+    }
+
+    /// Same as `invalidate`, for callers that only know the hash of the key (see `ApiKey::key_hash`).
+    pub fn invalidate_hash(&self, key_hash: &str) {
+        let mut cache = self.lock();
+        cache.entries.remove(key_hash);
+        cache.invalidations += 1;
+    }
+
+    pub fn clear(&self) {
+        let mut cache = self.lock();
+        cache.entries.clear();
+        cache.insertion_order.clear();
+        cache.invalidations += 1;
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Cache> {
+        match self.cache.lock() {
+            Ok(cache) => cache,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    fn remember(&self, key_hash: String, invalidations: u64, result: &Result<ApiKey, ApiKeyStorageError>) {
+        let mut cache = self.lock();
+
+        if cache.invalidations != invalidations {
+            cache.stop_refreshing(&key_hash);
+            return;
+        }
+
+        let now = self.clock.monotonic();
+
+        match result {
+            Ok(api_key) => cache.insert(key_hash, CachedValue::Found(api_key.clone()), &self.options, now),
+            Err(ApiKeyStorageError::KeyNotFound) => cache.insert(key_hash, CachedValue::Missing, &self.options, now),
+            Err(_) => cache.stop_refreshing(&key_hash),
+        }
+    }
+
+    fn refresh_in_background(&self, key: &str, key_hash: String, invalidations: u64) {
+        let storage = self.clone();
+        let key = key.to_string();
+
+        tokio::spawn(async move {
+            let result = storage.inner.retrieve_api_key(&key).await;
+
+            if let Err(e) = &result {
+                tracing::warn!("Unable to refresh cached api key {key_hash}: {}", ErrorChain(e));
+            }
+
+            storage.remember(key_hash, invalidations, &result);
+        });
+    }
+}
+
+#[async_trait]
+impl<S> ApiKeyStorage for CachedStorage<S>
+where
+    S: ApiKeyStorage + Clone + Send + Sync + 'static,
+{
+    async fn store_api_key(&mut self, key: &str, value: &ApiKey) -> Result<String, ApiKeyStorageError> {
+        let result = self.inner.store_api_key(key, value).await;
+
+        self.invalidate(key);
+
+        result
+    }
+
+    async fn retrieve_api_key(&self, key: &str) -> Result<ApiKey, ApiKeyStorageError> {
+        let key_hash = ApiKey::hash_key(key);
+
+        let (lookup, invalidations) = {
+            let mut cache = self.lock();
+            (cache.lookup(&key_hash, &self.options, self.clock.monotonic()), cache.invalidations)
+        };
+
+        match lookup {
+            Lookup::Fresh(CachedValue::Found(api_key)) => Ok(api_key),
+            Lookup::Fresh(CachedValue::Missing) => Err(ApiKeyStorageError::KeyNotFound),
+            Lookup::Stale(api_key, needs_refresh) => {
+                if needs_refresh {
+                    self.refresh_in_background(key, key_hash, invalidations);
+                }
+
+                Ok(api_key)
+            }
+            Lookup::Miss => {
+                let result = self.inner.retrieve_api_key(key).await;
+
+                self.remember(key_hash, invalidations, &result);
+
+                result
+            }
+        }
+    }
+
+    async fn delete_api_key(&mut self, key: &str) -> Result<bool, ApiKeyStorageError> {
+        let result = self.inner.delete_api_key(key).await;
+
+        self.invalidate(key);
+
+        result
+    }
+
+    fn use_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.inner.use_clock(clock.clone());
+        self.clock = clock;
+    }
+}
+
+impl<S> KeyChangeListener for CachedStorage<S>
+where
+    S: ApiKeyStorage + Clone + Send + Sync + 'static,
+{
+    fn key_changed(&self, event: &KeyChangeEvent) {
+        self.invalidate_hash(&event.key_hash);
+    }
+
+    fn resync(&self) {
+        self.clear();
+    }
+}
+
+/// Listing bypasses the cache.
+#[async_trait]
+impl<S> ApiKeyLister for CachedStorage<S>
+where
+    S: ApiKeyStorage + ApiKeyLister + Clone + Send + Sync + 'static,
+{
+    async fn list_api_keys(&self, cursor: Option<&str>, limit: usize) -> Result<ApiKeyPage, ApiKeyStorageError> {
+        self.inner.list_api_keys(cursor, limit).await
+    }
+}
@@ -0,0 +1,229 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use tokio::task::JoinHandle;
+
+use super::KeyIndex;
+use crate::{
+    clock::{system_clock, Clock},
+    errors::{ApiKeyStorageError, BoxError, ErrorChain, FileStorageError},
+    schema::CURRENT_SCHEMA_VERSION,
+    traits::{ApiKeyLister, ApiKeyStorage},
+    types::{ApiKey, ApiKeyLimits, ApiKeyPage, ApiKeyRestrictions, ApiKeyStatus},
+};
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeyFile {
+    keys: Vec<KeyDefinition>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeyDefinition {
+    key: String,
+    limits: ApiKeyLimits,
+    #[serde(default)]
+    restrictions: ApiKeyRestrictions,
+    #[serde(default = "default_status")]
+    status: ApiKeyStatus,
+    #[serde(default)]
+    owner: Option<String>,
+    created_at: Option<DateTime<Utc>>,
+    updated_at: Option<DateTime<Utc>>,
+}
+
+fn default_status() -> ApiKeyStatus {
+    ApiKeyStatus::Active
+}
+
+enum FileFormat {
+    Yaml,
+    Toml,
+    Json,
+}
+
+/// Read-only storage serving the keys defined in a YAML, TOML or JSON file.
+///
+/// The file contains a `keys` list whose entries look like `ApiKey`, except that `restrictions`,
+/// `status` (`Active`), `owner` and the timestamps (load time) may be left out. Definitions are
+/// validated as a whole when loading: an invalid file never replaces the keys currently served.
+#[derive(Clone)]
+pub struct FileStorage {
+    path: PathBuf,
+    keys: Arc<RwLock<Arc<KeyIndex>>>,
+    // Shared with the task started by `watch`, so that `use_clock` reaches it.
+    clock: Arc<RwLock<Arc<dyn Clock>>>,
+}
+
+impl FileStorage {
+    pub async fn new(path: impl AsRef<Path>) -> Result<Self, FileStorageError> {
+        Self::with_clock(path, system_clock()).await
+    }
+
+    /// Same as `new`, timestamps left out of the file being taken from `clock` instead of the system clock.
+    pub async fn with_clock(path: impl AsRef<Path>, clock: impl Clock + 'static) -> Result<Self, FileStorageError> {
+        let path = path.as_ref().to_path_buf();
+        let clock: Arc<dyn Clock> = Arc::new(clock);
+        let contents = Self::read(&path).await?;
+        let keys = Self::parse(&path, &contents, clock.now())?;
+
+        Ok(Self { path, keys: Arc::new(RwLock::new(Arc::new(keys))), clock: Arc::new(RwLock::new(clock)) })
+    }
+
+    /// Loads the file again and swaps the new definitions in. Returns the number of keys loaded.
+    pub async fn reload(&self) -> Result<usize, FileStorageError> {
+        let contents = Self::read(&self.path).await?;
+        self.swap(&contents)
+    }
+
+    /// Polls the file every `interval` and reloads it whenever its contents change. Errors are
+    /// logged and the previous definitions keep being served until the file is fixed.
+    pub fn watch(&self, interval: Duration) -> JoinHandle<()> {
+        let storage = self.clone();
+
+        tokio::spawn(async move {
+            // The first tick reloads unconditionally so that changes made before the task starts are not missed.
+            let mut last_contents = None;
+            let mut interval = tokio::time::interval(interval);
+
+            loop {
+                interval.tick().await;
+
+                let contents = match Self::read(&storage.path).await {
+                    Ok(contents) => contents,
+                    Err(e) => {
+                        tracing::error!("Unable to reload api keys: {}", ErrorChain(&e));
+                        continue;
+                    }
+                };
+
+                if last_contents.as_ref() == Some(&contents) {
+                    continue;
+                }
+
+                match storage.swap(&contents) {
+                    Ok(count) => tracing::info!("Reloaded {count} api keys from {}", storage.path.display()),
+                    Err(e) => tracing::error!("Unable to reload api keys: {}", ErrorChain(&e)),
+                }
+
+                last_contents = Some(contents);
+            }
+        })
+    }
+
+    fn swap(&self, contents: &str) -> Result<usize, FileStorageError> {
+        let keys = Self::parse(&self.path, contents, self.now())?;
+        let count = keys.len();
+
+        match self.keys.write() {
+            Ok(mut current) => *current = Arc::new(keys),
+            Err(poisoned) => *poisoned.into_inner() = Arc::new(keys),
+        }
+
+        Ok(count)
+    }
+
+    fn now(&self) -> DateTime<Utc> {
+        match self.clock.read() {
+            Ok(clock) => clock.now(),
+            Err(poisoned) => poisoned.into_inner().now(),
+        }
+    }
+
+    fn snapshot(&self) -> Arc<KeyIndex> {
+        match self.keys.read() {
+            Ok(keys) => keys.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    async fn read(path: &Path) -> Result<String, FileStorageError> {
+        tokio::fs::read_to_string(path).await.map_err(|e| FileStorageError::Io(path.to_path_buf(), e))
+    }
+
+    fn parse(path: &Path, contents: &str, loaded_at: DateTime<Utc>) -> Result<KeyIndex, FileStorageError> {
+        let format = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("yaml" | "yml") => FileFormat::Yaml,
+            Some("toml") => FileFormat::Toml,
+            Some("json") => FileFormat::Json,
+            _ => return Err(FileStorageError::UnsupportedFormat(path.to_path_buf())),
+        };
+
+        let file: KeyFile = match format {
+            FileFormat::Yaml => serde_yaml::from_str(contents).map_err(BoxError::from),
+            FileFormat::Toml => toml::from_str(contents).map_err(BoxError::from),
+            FileFormat::Json => serde_json::from_str(contents).map_err(BoxError::from),
+        }
+        .map_err(|e| FileStorageError::Parse(path.to_path_buf(), e))?;
+
+        let mut keys = KeyIndex::default();
+
+        for (index, definition) in file.keys.into_iter().enumerate() {
+            let invalid = |message: String| FileStorageError::InvalidKey { path: path.to_path_buf(), index, message };
+
+            if definition.key.trim().is_empty() {
+                return Err(invalid("the key must not be empty".to_string()));
+            }
+
+            if keys.contains_key(&definition.key) {
+                return Err(invalid("the same key is already defined by an earlier entry".to_string()));
+            }
+
+            let created_at = definition.created_at.unwrap_or(loaded_at);
+
+            let api_key = ApiKey {
+                key: definition.key,
+                limits: definition.limits,
+                restrictions: definition.restrictions,
+                status: definition.status,
+                owner: definition.owner,
+                created_at,
+                updated_at: definition.updated_at.unwrap_or(created_at),
+                schema_version: CURRENT_SCHEMA_VERSION,
+            };
+
+            keys.insert(api_key.key.clone(), api_key);
+        }
+
+        Ok(keys)
+    }
+}
+
+#[async_trait]
+impl ApiKeyStorage for FileStorage {
+    async fn store_api_key(&mut self, _key: &str, _value: &ApiKey) -> Result<String, ApiKeyStorageError> {
+        Err(ApiKeyStorageError::ReadOnly)
+    }
+
+    async fn retrieve_api_key(&self, key: &str) -> Result<ApiKey, ApiKeyStorageError> {
+        match self.snapshot().get(key) {
+            Some(api_key) => Ok(api_key.clone()),
+            None => Err(ApiKeyStorageError::KeyNotFound),
+        }
+    }
+
+    async fn delete_api_key(&mut self, _key: &str) -> Result<bool, ApiKeyStorageError> {
+        Err(ApiKeyStorageError::ReadOnly)
+    }
+
+    fn use_clock(&mut self, clock: Arc<dyn Clock>) {
+        match self.clock.write() {
+            Ok(mut current) => *current = clock,
+            Err(poisoned) => *poisoned.into_inner() = clock,
+        }
+    }
+}
+
+#[async_trait]
+impl ApiKeyLister for FileStorage {
+    async fn list_api_keys(&self, cursor: Option<&str>, limit: usize) -> Result<ApiKeyPage, ApiKeyStorageError> {
+        Ok(self.snapshot().page(cursor, limit))
+    }
+}
@@ -1,36 +1,69 @@
-use std::collections::HashMap;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
 
 use async_trait::async_trait;
 
-use crate::{errors::ApiKeyStorageError, traits::ApiKeyStorage, types::ApiKey};
+use super::KeyIndex;
+use crate::{
+    errors::ApiKeyStorageError,
+    traits::{ApiKeyLister, ApiKeyStorage},
+    types::{ApiKey, ApiKeyPage},
+};
 
+/// Keeps the keys in memory. Clones share the same keys.
 #[derive(Clone, Default)]
 pub struct HashMapStorage {
-    map: HashMap<String, ApiKey>,
+    map: Arc<RwLock<KeyIndex>>,
 }
 
 impl HashMapStorage {
     pub fn new() -> Self {
-        Self { map: HashMap::default() }
+        Self { map: Arc::default() }
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, KeyIndex> {
+        match self.map.read() {
+            Ok(map) => map,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, KeyIndex> {
+        match self.map.write() {
+            Ok(map) => map,
+            Err(poisoned) => poisoned.into_inner(),
+        }
     }
 }
 
 #[async_trait]
 impl ApiKeyStorage for HashMapStorage {
     async fn store_api_key(&mut self, key: &str, value: &ApiKey) -> Result<String, ApiKeyStorageError> {
-        self.map.insert(key.to_string(), value.clone());
+        let mut map = self.write();
+
+        if map.contains_key(key) {
+            return Err(ApiKeyStorageError::KeyAlreadyExists);
+        }
+
+        map.insert(key.to_string(), value.clone());
 
         Ok(key.to_string())
     }
 
     async fn retrieve_api_key(&self, key: &str) -> Result<ApiKey, ApiKeyStorageError> {
-        match self.map.get(key).cloned() {
+        match self.read().get(key).cloned() {
             Some(api_key) => Ok(api_key),
             None => Err(ApiKeyStorageError::KeyNotFound),
         }
     }
 
     async fn delete_api_key(&mut self, key: &str) -> Result<bool, ApiKeyStorageError> {
-        Ok(self.map.remove(key).is_some())
+        Ok(self.write().remove(key).is_some())
+    }
+}
+
+#[async_trait]
+impl ApiKeyLister for HashMapStorage {
+    async fn list_api_keys(&self, cursor: Option<&str>, limit: usize) -> Result<ApiKeyPage, ApiKeyStorageError> {
+        Ok(self.read().page(cursor, limit))
     }
 }
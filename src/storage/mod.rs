@@ -1,2 +1,90 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    ops::Bound,
+};
+
+#[cfg(any(feature = "postgres", feature = "sqlite"))]
+use crate::errors::ApiKeyStorageError;
+use crate::types::{ApiKey, ApiKeyPage};
+
+pub mod cached_storage;
+#[cfg(feature = "file")]
+pub mod file_storage;
 pub mod memory_storage;
+#[cfg(feature = "mongodb")]
 pub mod mongodb_storage;
+#[cfg(feature = "postgres")]
+pub mod postgres_storage;
+#[cfg(feature = "redis")]
+pub mod redis_storage;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_storage;
+pub mod tiered_storage;
+
+/// Keys held in memory, along with their hashes in order so that paging through them does not sort
+/// every key on every page. The hash of a key is also the cursor.
+#[derive(Default)]
+pub(crate) struct KeyIndex {
+    keys: HashMap<String, ApiKey>,
+    hashes: BTreeMap<String, String>,
+}
+
+impl KeyIndex {
+    pub(crate) fn get(&self, key: &str) -> Option<&ApiKey> {
+        self.keys.get(key)
+    }
+
+    pub(crate) fn contains_key(&self, key: &str) -> bool {
+        self.keys.contains_key(key)
+    }
+
+    #[cfg(feature = "file")]
+    pub(crate) fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub(crate) fn insert(&mut self, key: String, api_key: ApiKey) -> Option<ApiKey> {
+        self.hashes.insert(ApiKey::hash_key(&key), key.clone());
+        self.keys.insert(key, api_key)
+    }
+
+    pub(crate) fn remove(&mut self, key: &str) -> Option<ApiKey> {
+        let removed = self.keys.remove(key)?;
+        self.hashes.remove(&ApiKey::hash_key(key));
+
+        Some(removed)
+    }
+
+    pub(crate) fn page(&self, cursor: Option<&str>, limit: usize) -> ApiKeyPage {
+        let after = match cursor {
+            Some(cursor) => Bound::Excluded(cursor),
+            None => Bound::Unbounded,
+        };
+
+        let hashed: Vec<(&String, &ApiKey)> = self
+            .hashes
+            .range::<str, _>((after, Bound::Unbounded))
+            .filter_map(|(key_hash, key)| self.keys.get(key).map(|api_key| (key_hash, api_key)))
+            .take(limit)
+            .collect();
+
+        let next_cursor = match hashed.len() == limit {
+            true => hashed.last().map(|(key_hash, _)| key_hash.to_string()),
+            false => None,
+        };
+
+        ApiKeyPage { api_keys: hashed.into_iter().map(|(_, api_key)| api_key.clone()).collect(), next_cursor }
+    }
+}
+
+#[cfg(any(feature = "postgres", feature = "sqlite"))]
+impl From<sqlx::Error> for ApiKeyStorageError {
+    fn from(error: sqlx::Error) -> Self {
+        match error {
+            sqlx::Error::ColumnDecode { .. } | sqlx::Error::Decode(_) => {
+                ApiKeyStorageError::SerializationError(error.into())
+            }
+            error => ApiKeyStorageError::StorageError(error.into()),
+        }
+    }
+}
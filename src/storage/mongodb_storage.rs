@@ -1,13 +1,40 @@
 use async_trait::async_trait;
-use bson::doc;
-use mongodb::{options::ClientOptions, Client, Database};
+use bson::{doc, oid::ObjectId, Bson, Document};
+use futures_util::{StreamExt, TryStreamExt};
+use mongodb::{
+    change_stream::event::{ChangeStreamEvent, OperationType, ResumeToken},
+    error::{ErrorKind, WriteFailure},
+    options::{ClientOptions, FindOptions, FullDocumentBeforeChangeType, FullDocumentType, IndexOptions},
+    Client, Database, IndexModel,
+};
+use tokio::task::JoinHandle;
 
-use crate::{errors::ApiKeyStorageError, traits::ApiKeyStorage, types::ApiKey};
+use crate::{
+    errors::ApiKeyStorageError,
+    invalidation::{KeyChangeEvent, KeyChangeKind, KeyChangeListener, MAX_RECONNECT_DELAY, MIN_RECONNECT_DELAY},
+    schema::{self, CURRENT_SCHEMA_VERSION},
+    traits::{ApiKeyLister, ApiKeyStorage},
+    types::{ApiKey, ApiKeyPage},
+};
 
+// The resume token is too old to resume from: the events in between have left the oplog.
+const CHANGE_STREAM_FATAL_ERROR: i32 = 280;
+const CHANGE_STREAM_HISTORY_LOST: i32 = 286;
+const NAMESPACE_NOT_FOUND: i32 = 26;
+const DUPLICATE_KEY: i32 = 11000;
+
+/// Stores API keys as documents of a MongoDB collection (`api_keys` by default).
+///
+/// `new` creates the indexes of the collection (see `create_indexes`): the unique index on `key` is
+/// what rejects concurrent creations of the same key.
+///
+/// Documents stored by older versions of this crate are upgraded when they are read (see `schema`),
+/// and written back in the current shape if `with_outdated_record_rewrites` is enabled.
 #[derive(Clone)]
 pub struct MongoDBStorage {
     db: Database,
     collection_name: String,
+    rewrite_outdated_records: bool,
 }
 
 impl MongoDBStorage {
@@ -15,6 +42,20 @@ impl MongoDBStorage {
         uri: &str,
         db_name: &str,
         collection_name: Option<String>,
+    ) -> Result<Self, mongodb::error::Error> {
+        let storage = Self::connect(uri, db_name, collection_name).await?;
+
+        storage.create_indexes().await?;
+
+        Ok(storage)
+    }
+
+    /// Same as `new` without creating the indexes. The client connects lazily, so this does not
+    /// reach the server.
+    pub(crate) async fn connect(
+        uri: &str,
+        db_name: &str,
+        collection_name: Option<String>,
     ) -> Result<Self, mongodb::error::Error> {
         let client_options = ClientOptions::parse(uri).await?;
         let client = Client::with_options(client_options)?;
@@ -26,8 +67,264 @@ impl MongoDBStorage {
                 Some(name) => name,
                 None => "api_keys".to_string(),
             },
+            rewrite_outdated_records: false,
         })
     }
+
+    pub fn with_outdated_record_rewrites(mut self, enabled: bool) -> Self {
+        self.rewrite_outdated_records = enabled;
+        self
+    }
+
+    async fn read_document(&self, document: Document) -> Result<ApiKey, ApiKeyStorageError> {
+        let id = document.get("_id").cloned();
+        let upcasted = schema::upcast(Bson::Document(document).into_relaxed_extjson())?;
+
+        if upcasted.is_outdated() && self.rewrite_outdated_records {
+            self.rewrite(id, &upcasted.api_key, upcasted.stored_version).await;
+        }
+
+        Ok(upcasted.api_key)
+    }
+
+    /// Replaces an outdated document, unless it was changed since it was read. Failures are only
+    /// logged since the document is upgraded on every read anyway.
+    async fn rewrite(&self, id: Option<Bson>, api_key: &ApiKey, stored_version: u32) {
+        let collection = self.db.collection::<ApiKey>(self.collection_name.as_str());
+
+        // Unversioned documents have no `schema_version`, which `null` matches.
+        let stored_version = match stored_version {
+            0 => Bson::Null,
+            version => Bson::from(version),
+        };
+
+        let filter = doc! { "_id": id, "schema_version": stored_version };
+
+        if let Err(e) = collection.replace_one(filter, api_key).await {
+            tracing::warn!("Unable to rewrite outdated api key {}: {e}", api_key.key_hash());
+        }
+    }
+
+    /// Creates a unique index on `key` and indexes on `status` and `owner`. Called by `new`, safe to
+    /// run again. Fails if the collection already holds duplicate keys.
+    pub async fn create_indexes(&self) -> Result<(), mongodb::error::Error> {
+        let collection = self.db.collection::<Document>(self.collection_name.as_str());
+
+        let indexes = vec![
+            IndexModel::builder()
+                .keys(doc! { "key": 1 })
+                .options(IndexOptions::builder().name("key_unique".to_string()).unique(true).build())
+                .build(),
+            IndexModel::builder()
+                .keys(doc! { "status": 1 })
+                .options(IndexOptions::builder().name("status".to_string()).build())
+                .build(),
+            IndexModel::builder()
+                .keys(doc! { "owner": 1 })
+                .options(IndexOptions::builder().name("owner".to_string()).build())
+                .build(),
+        ];
+
+        collection.create_indexes(indexes).await?;
+
+        Ok(())
+    }
+
+    /// Records the previous version of changed documents, so that change events of deleted keys
+    /// can name the key (see `watch_changes`). Requires MongoDB 6.0, creates the collection if needed.
+    pub async fn enable_change_stream_pre_images(&self) -> Result<(), mongodb::error::Error> {
+        let pre_images = doc! { "enabled": true };
+
+        let result = self
+            .db
+            .run_command(doc! { "collMod": &self.collection_name, "changeStreamPreAndPostImages": pre_images.clone() })
+            .await;
+
+        match result {
+            Err(e) if command_error_code(&e) == Some(NAMESPACE_NOT_FOUND) => {
+                self.db
+                    .run_command(doc! { "create": &self.collection_name, "changeStreamPreAndPostImages": pre_images })
+                    .await?;
+                Ok(())
+            }
+            result => result.map(|_| ()),
+        }
+    }
+
+    /// Follows the change stream of the collection in a background task and forwards the inserted,
+    /// updated and deleted keys to `listener`, usually a `CachedStorage`. Change streams require a
+    /// replica set or a sharded cluster.
+    ///
+    /// After a disconnect the stream resumes from the last event seen. When that is not possible, or
+    /// when an event does not say which key changed (deletes without pre-images, dropped
+    /// collections), the listener is told to `resync` instead.
+    pub fn watch_changes(&self, listener: impl KeyChangeListener) -> JoinHandle<()> {
+        let storage = self.clone();
+
+        tokio::spawn(async move {
+            let mut resume_token = None;
+            let mut reconnect_delay = MIN_RECONNECT_DELAY;
+
+            loop {
+                match storage.follow_changes(&listener, &mut resume_token).await {
+                    Ok(()) => {
+                        tracing::warn!("The change stream of {} was closed, reopening it", storage.collection_name);
+                        reconnect_delay = MIN_RECONNECT_DELAY;
+                    }
+                    Err(e) => {
+                        let code = command_error_code(&e);
+                        if matches!(code, Some(CHANGE_STREAM_FATAL_ERROR | CHANGE_STREAM_HISTORY_LOST)) {
+                            resume_token = None;
+                        }
+
+                        tracing::error!("Unable to follow the changes of {}: {e}", storage.collection_name);
+                        reconnect_delay = (reconnect_delay * 2).min(MAX_RECONNECT_DELAY);
+                    }
+                }
+
+                tokio::time::sleep(reconnect_delay).await;
+            }
+        })
+    }
+
+    /// Returns once the stream is closed, keeping `resume_token` up to date with the events seen.
+    async fn follow_changes(
+        &self,
+        listener: &impl KeyChangeListener,
+        resume_token: &mut Option<ResumeToken>,
+    ) -> Result<(), mongodb::error::Error> {
+        let collection = self.db.collection::<Document>(self.collection_name.as_str());
+
+        let mut watch = collection
+            .watch()
+            .full_document(FullDocumentType::UpdateLookup)
+            .full_document_before_change(FullDocumentBeforeChangeType::WhenAvailable);
+
+        let resuming = resume_token.is_some();
+
+        if let Some(token) = resume_token.clone() {
+            watch = watch.start_after(token);
+        }
+
+        let mut stream = watch.await?;
+
+        // Without a token to resume from, the changes made before this point were never seen.
+        if !resuming {
+            listener.resync();
+        }
+
+        *resume_token = stream.resume_token();
+
+        while let Some(event) = stream.next().await {
+            match key_changes(&event?) {
+                Some(changes) => changes.iter().for_each(|change| listener.key_changed(change)),
+                None => listener.resync(),
+            }
+
+            *resume_token = stream.resume_token();
+        }
+
+        Ok(())
+    }
+}
+
+/// Keys are listed in the order of their `_id`, which is used as the cursor.
+#[async_trait]
+impl ApiKeyLister for MongoDBStorage {
+    async fn list_api_keys(&self, cursor: Option<&str>, limit: usize) -> Result<ApiKeyPage, ApiKeyStorageError> {
+        // MongoDB treats a limit of zero as no limit at all.
+        if limit == 0 {
+            return Ok(ApiKeyPage::default());
+        }
+
+        let collection = self.db.collection::<Document>(self.collection_name.as_str());
+
+        let filter = match cursor {
+            Some(cursor) => {
+                let id = ObjectId::parse_str(cursor).map_err(|e| ApiKeyStorageError::StorageError(e.into()))?;
+                doc! { "_id": { "$gt": id } }
+            }
+            None => doc! {},
+        };
+
+        let options = FindOptions::builder().sort(doc! { "_id": 1 }).limit(limit as i64).build();
+
+        let documents: Vec<Document> = collection
+            .find(filter)
+            .with_options(options)
+            .await
+            .map_err(|e| ApiKeyStorageError::StorageError(e.into()))?
+            .try_collect()
+            .await
+            .map_err(|e| ApiKeyStorageError::StorageError(e.into()))?;
+
+        let next_cursor = match documents.len() == limit {
+            true => documents.last().and_then(|document| document.get_object_id("_id").ok()).map(|id| id.to_hex()),
+            false => None,
+        };
+
+        let mut api_keys = Vec::with_capacity(documents.len());
+
+        for document in documents {
+            api_keys.push(self.read_document(document).await?);
+        }
+
+        Ok(ApiKeyPage { api_keys, next_cursor })
+    }
+}
+
+fn command_error_code(error: &mongodb::error::Error) -> Option<i32> {
+    match error.kind.as_ref() {
+        ErrorKind::Command(command_error) => Some(command_error.code),
+        _ => None,
+    }
+}
+
+fn is_duplicate_key_error(error: &mongodb::error::Error) -> bool {
+    match error.kind.as_ref() {
+        ErrorKind::Write(WriteFailure::WriteError(write_error)) => write_error.code == DUPLICATE_KEY,
+        ErrorKind::Command(command_error) => command_error.code == DUPLICATE_KEY,
+        _ => false,
+    }
+}
+
+/// Returns `None` when the event does not say which keys changed.
+fn key_changes(event: &ChangeStreamEvent<Document>) -> Option<Vec<KeyChangeEvent>> {
+    let key = |document: &Option<Document>| {
+        document.as_ref().and_then(|document| document.get_str("key").ok()).map(ApiKey::hash_key)
+    };
+
+    let before = key(&event.full_document_before_change);
+    let after = key(&event.full_document);
+
+    let changes = match event.operation_type {
+        OperationType::Insert => vec![after.map(|key_hash| KeyChangeEvent::new(key_hash, KeyChangeKind::Stored))?],
+        OperationType::Update | OperationType::Replace => {
+            let mut changes = Vec::new();
+
+            // The key itself may have been changed, in which case both versions are stale.
+            for key_hash in [&before, &after].into_iter().flatten() {
+                if !changes.iter().any(|change: &KeyChangeEvent| &change.key_hash == key_hash) {
+                    changes.push(KeyChangeEvent::new(key_hash.clone(), KeyChangeKind::Updated));
+                }
+            }
+
+            // Without a pre-image, an update that removed the document before it could be looked up
+            // leaves both versions unknown.
+            if changes.is_empty() {
+                return None;
+            }
+
+            changes
+        }
+        OperationType::Delete => vec![before.map(|key_hash| KeyChangeEvent::new(key_hash, KeyChangeKind::Deleted))?],
+        OperationType::Drop | OperationType::Rename | OperationType::DropDatabase | OperationType::Invalidate => {
+            return None
+        }
+        _ => vec![],
+    };
+
+    Some(changes)
 }
 
 #[async_trait]
@@ -37,43 +334,38 @@ impl ApiKeyStorage for MongoDBStorage {
         key: &str,
         value: &ApiKey,
     ) -> Result<String, ApiKeyStorageError> {
+        if key != value.key {
+            return Err(ApiKeyStorageError::KeyMismatch);
+        }
+
         let collection = self.db.collection::<ApiKey>(self.collection_name.as_str());
 
-        let filter = doc! { "key": key };
+        let record = ApiKey { schema_version: CURRENT_SCHEMA_VERSION, ..value.clone() };
 
-        match collection.find_one(filter).await {
-            Ok(result) => {
-                if result.is_some() {
-                    return Err(ApiKeyStorageError::KeyAlreadyExists);
-                }
-            }
-            Err(e) => return Err(ApiKeyStorageError::StorageError(e.to_string())),
+        // Duplicates are rejected by the unique index, see `create_indexes`.
+        match collection.insert_one(record).await {
+            Ok(_) => Ok(key.to_string()),
+            Err(e) if is_duplicate_key_error(&e) => Err(ApiKeyStorageError::KeyAlreadyExists),
+            Err(e) => Err(ApiKeyStorageError::StorageError(e.into())),
         }
-
-        match collection.insert_one(value).await {
-            Ok(result) => result,
-            Err(e) => return Err(ApiKeyStorageError::StorageError(e.to_string())),
-        };
-
-        Ok(key.to_string())
     }
 
     async fn retrieve_api_key(&self, key: &str) -> Result<ApiKey, ApiKeyStorageError> {
-        let collection = self.db.collection::<ApiKey>(self.collection_name.as_str());
+        let collection = self.db.collection::<Document>(self.collection_name.as_str());
 
         let filter = doc! { "key": key };
 
         let result = collection.find_one(filter).await;
 
-        let api_key = match result {
+        let document = match result {
             Ok(result) => match result {
                 Some(doc) => doc,
                 None => return Err(ApiKeyStorageError::KeyNotFound),
             },
-            Err(e) => return Err(ApiKeyStorageError::StorageError(e.to_string())),
+            Err(e) => return Err(ApiKeyStorageError::StorageError(e.into())),
         };
 
-        Ok(api_key)
+        self.read_document(document).await
     }
 
     async fn delete_api_key(&mut self, key: &str) -> Result<bool, ApiKeyStorageError> {
@@ -85,7 +377,7 @@ impl ApiKeyStorage for MongoDBStorage {
 
         match result {
             Ok(result) => Ok(result.deleted_count > 0),
-            Err(e) => return Err(ApiKeyStorageError::StorageError(e.to_string())),
+            Err(e) => return Err(ApiKeyStorageError::StorageError(e.into())),
         }
     }
 }
@@ -0,0 +1,132 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{
+    migrate::{MigrateError, Migrator},
+    postgres::{PgPool, PgPoolOptions, PgRow},
+    types::Json,
+    Row,
+};
+
+use crate::{
+    errors::ApiKeyStorageError,
+    schema::CURRENT_SCHEMA_VERSION,
+    traits::{ApiKeyLister, ApiKeyStorage},
+    types::{ApiKey, ApiKeyLimits, ApiKeyPage, ApiKeyRestrictions, ApiKeyStatus},
+};
+
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations/postgres");
+
+/// Stores API keys in the `api_keys` table created by the bundled migrations.
+///
+/// Keys are looked up through a unique index on the SHA-256 of the key, limits and restrictions
+/// are stored as JSONB.
+#[derive(Clone)]
+pub struct PostgresStorage {
+    pool: PgPool,
+}
+
+impl PostgresStorage {
+    pub async fn new(uri: &str) -> Result<Self, sqlx::Error> {
+        let pool = PgPoolOptions::new().connect(uri).await?;
+
+        Ok(Self::from_pool(pool))
+    }
+
+    pub fn from_pool(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Creates or upgrades the `api_keys` table. Safe to run on every start.
+    pub async fn migrate(&self) -> Result<(), MigrateError> {
+        MIGRATOR.run(&self.pool).await
+    }
+
+    fn api_key_from_row(row: PgRow) -> Result<ApiKey, ApiKeyStorageError> {
+        let status: String = row.try_get("status")?;
+
+        Ok(ApiKey {
+            key: row.try_get("key")?,
+            limits: row.try_get::<Json<ApiKeyLimits>, _>("limits")?.0,
+            restrictions: row.try_get::<Json<ApiKeyRestrictions>, _>("restrictions")?.0,
+            status: status.parse::<ApiKeyStatus>().map_err(|e| ApiKeyStorageError::SerializationError(e.into()))?,
+            owner: row.try_get("owner")?,
+            created_at: row.try_get::<DateTime<Utc>, _>("created_at")?,
+            updated_at: row.try_get::<DateTime<Utc>, _>("updated_at")?,
+            // Columns are upgraded by the migrations, so rows are always in the current shape.
+            schema_version: CURRENT_SCHEMA_VERSION,
+        })
+    }
+}
+
+#[async_trait]
+impl ApiKeyStorage for PostgresStorage {
+    async fn store_api_key(&mut self, key: &str, value: &ApiKey) -> Result<String, ApiKeyStorageError> {
+        let result = sqlx::query(
+            "INSERT INTO api_keys (key, key_hash, limits, restrictions, status, owner, created_at, updated_at) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8) \
+             ON CONFLICT (key_hash) DO NOTHING",
+        )
+        .bind(key)
+        .bind(ApiKey::hash_key(key))
+        .bind(Json(&value.limits))
+        .bind(Json(&value.restrictions))
+        .bind(value.status.to_string())
+        .bind(&value.owner)
+        .bind(value.created_at)
+        .bind(value.updated_at)
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(ApiKeyStorageError::KeyAlreadyExists);
+        }
+
+        Ok(key.to_string())
+    }
+
+    async fn retrieve_api_key(&self, key: &str) -> Result<ApiKey, ApiKeyStorageError> {
+        let row = sqlx::query(
+            "SELECT key, limits, restrictions, status, owner, created_at, updated_at FROM api_keys WHERE key_hash = $1",
+        )
+        .bind(ApiKey::hash_key(key))
+        .fetch_optional(&self.pool)
+        .await?;
+
+        match row {
+            Some(row) => Self::api_key_from_row(row),
+            None => Err(ApiKeyStorageError::KeyNotFound),
+        }
+    }
+
+    async fn delete_api_key(&mut self, key: &str) -> Result<bool, ApiKeyStorageError> {
+        let result = sqlx::query("DELETE FROM api_keys WHERE key_hash = $1")
+            .bind(ApiKey::hash_key(key))
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+#[async_trait]
+impl ApiKeyLister for PostgresStorage {
+    async fn list_api_keys(&self, cursor: Option<&str>, limit: usize) -> Result<ApiKeyPage, ApiKeyStorageError> {
+        let rows = sqlx::query(
+            "SELECT key_hash, key, limits, restrictions, status, owner, created_at, updated_at FROM api_keys \
+             WHERE key_hash > $1 ORDER BY key_hash LIMIT $2",
+        )
+        .bind(cursor.unwrap_or_default())
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let next_cursor = match rows.len() == limit {
+            true => rows.last().map(|row| row.try_get::<String, _>("key_hash")).transpose()?,
+            false => None,
+        };
+
+        let api_keys = rows.into_iter().map(Self::api_key_from_row).collect::<Result<_, _>>()?;
+
+        Ok(ApiKeyPage { api_keys, next_cursor })
+    }
+}
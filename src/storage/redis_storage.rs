@@ -0,0 +1,232 @@
+use async_trait::async_trait;
+use redis::{AsyncCommands, ExistenceCheck, RedisError, SetOptions};
+
+use crate::{
+    errors::ApiKeyStorageError,
+    redis_connection::{RedisConnection, RedisKeyspace, RedisTimeouts},
+    schema::{self, CURRENT_SCHEMA_VERSION},
+    traits::{ApiKeyLister, ApiKeyStorage},
+    types::{ApiKey, ApiKeyPage, ApiKeyStatus},
+};
+
+/// Stores API keys in Redis as JSON, under a name derived from the SHA-256 of the key.
+///
+/// Besides the records, the storage maintains one set per status and one set per owner holding
+/// the hashes of the matching keys, which back `list_api_keys_by_status` and
+/// `list_api_keys_by_owner`, and a sorted set of every hash which backs `list_api_keys`.
+#[derive(Clone)]
+pub struct RedisStorage {
+    connection: RedisConnection,
+    keyspace: RedisKeyspace,
+}
+
+impl RedisStorage {
+    pub async fn new(uri: &str) -> Result<Self, RedisError> {
+        Self::with_timeouts(uri, RedisTimeouts::default()).await
+    }
+
+    pub async fn with_timeouts(uri: &str, timeouts: RedisTimeouts) -> Result<Self, RedisError> {
+        let connection = RedisConnection::connect(uri, timeouts).await?;
+        Ok(Self::from_connection(connection))
+    }
+
+    pub fn from_connection(connection: RedisConnection) -> Self {
+        Self { connection, keyspace: RedisKeyspace::default() }
+    }
+
+    pub fn with_keyspace(mut self, keyspace: RedisKeyspace) -> Self {
+        self.keyspace = keyspace;
+        self
+    }
+
+    pub async fn list_api_keys_by_status(&self, status: &ApiKeyStatus) -> Result<Vec<ApiKey>, ApiKeyStorageError> {
+        self.list_index(self.status_index_key(status)).await
+    }
+
+    pub async fn list_api_keys_by_owner(&self, owner: &str) -> Result<Vec<ApiKey>, ApiKeyStorageError> {
+        self.list_index(self.owner_index_key(owner)).await
+    }
+
+    /// Adds the keys stored before `list_api_keys` was backed by the sorted set of hashes to that
+    /// set. Only needed once, after upgrading.
+    pub async fn rebuild_hash_index(&self) -> Result<(), ApiKeyStorageError> {
+        let mut connection = self.connection.clone();
+
+        for status in [ApiKeyStatus::Active, ApiKeyStatus::Inactive, ApiKeyStatus::Deleted] {
+            let key_hashes: Vec<String> = connection.smembers(self.status_index_key(&status)).await?;
+
+            for key_hash in key_hashes {
+                let _: i32 = connection.zadd(self.hash_index_key(), &key_hash, 0).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn record_key(&self, key_hash: &str) -> String {
+        self.keyspace.key(key_hash, "record")
+    }
+
+    /// Sorted set holding every hash with the same score, so that it is ordered by hash.
+    fn hash_index_key(&self) -> String {
+        self.keyspace.index_key("hash", "all")
+    }
+
+    fn status_index_key(&self, status: &ApiKeyStatus) -> String {
+        self.keyspace.index_key("status", &status.to_string())
+    }
+
+    fn owner_index_key(&self, owner: &str) -> String {
+        self.keyspace.index_key("owner", owner)
+    }
+
+    fn index_keys(&self, api_key: &ApiKey) -> Vec<String> {
+        let mut index_keys = vec![self.status_index_key(&api_key.status)];
+
+        if let Some(owner) = &api_key.owner {
+            index_keys.push(self.owner_index_key(owner));
+        }
+
+        index_keys
+    }
+
+    async fn get_record(&self, key_hash: &str) -> Result<Option<ApiKey>, ApiKeyStorageError> {
+        let mut connection = self.connection.clone();
+
+        let record: Option<String> = connection.get(self.record_key(key_hash)).await?;
+
+        match record {
+            Some(record) => match serde_json::from_str(&record) {
+                // Records stored by older versions are upgraded, see `schema`.
+                Ok(record) => Ok(Some(schema::upcast(record)?.api_key)),
+                Err(e) => Err(ApiKeyStorageError::SerializationError(e.into())),
+            },
+            None => Ok(None),
+        }
+    }
+
+    async fn list_index(&self, index_key: String) -> Result<Vec<ApiKey>, ApiKeyStorageError> {
+        let mut connection = self.connection.clone();
+
+        let key_hashes: Vec<String> = connection.smembers(&index_key).await?;
+
+        let mut api_keys = Vec::with_capacity(key_hashes.len());
+
+        for key_hash in key_hashes {
+            match self.get_record(&key_hash).await? {
+                Some(api_key) => api_keys.push(api_key),
+                // The record was deleted without its index entry, e.g. by a writer that crashed halfway.
+                None => {
+                    let _: i32 = connection.srem(&index_key, &key_hash).await?;
+                }
+            }
+        }
+
+        Ok(api_keys)
+    }
+}
+
+/// Keys are listed in the order of their hash, which is used as the cursor. A page is read from the
+/// sorted set of hashes with `ZRANGEBYLEX`, starting right after the cursor.
+#[async_trait]
+impl ApiKeyLister for RedisStorage {
+    async fn list_api_keys(&self, cursor: Option<&str>, limit: usize) -> Result<ApiKeyPage, ApiKeyStorageError> {
+        let mut connection = self.connection.clone();
+
+        let min = match cursor {
+            Some(cursor) => format!("({cursor}"),
+            None => "-".to_string(),
+        };
+
+        let key_hashes: Vec<String> = match limit {
+            0 => Vec::new(),
+            limit => connection.zrangebylex_limit(self.hash_index_key(), min, "+", 0, limit as isize).await?,
+        };
+
+        let next_cursor = match key_hashes.len() == limit {
+            true => key_hashes.last().cloned(),
+            false => None,
+        };
+
+        let mut api_keys = Vec::with_capacity(key_hashes.len());
+
+        for key_hash in &key_hashes {
+            match self.get_record(key_hash).await? {
+                Some(api_key) => api_keys.push(api_key),
+                // The record was deleted without its index entry, e.g. by a writer that crashed halfway.
+                None => {
+                    let _: i32 = connection.zrem(self.hash_index_key(), key_hash).await?;
+                }
+            }
+        }
+
+        Ok(ApiKeyPage { api_keys, next_cursor })
+    }
+}
+
+#[async_trait]
+impl ApiKeyStorage for RedisStorage {
+    async fn store_api_key(&mut self, key: &str, value: &ApiKey) -> Result<String, ApiKeyStorageError> {
+        if key != value.key {
+            return Err(ApiKeyStorageError::KeyMismatch);
+        }
+
+        let mut connection = self.connection.clone();
+
+        let key_hash = ApiKey::hash_key(key);
+        let record = ApiKey { schema_version: CURRENT_SCHEMA_VERSION, ..value.clone() };
+        let record =
+            serde_json::to_string(&record).map_err(|e| ApiKeyStorageError::SerializationError(e.into()))?;
+
+        let stored: Option<String> = connection
+            .set_options(self.record_key(&key_hash), record, SetOptions::default().conditional_set(ExistenceCheck::NX))
+            .await?;
+
+        if stored.is_none() {
+            return Err(ApiKeyStorageError::KeyAlreadyExists);
+        }
+
+        // Index sets live on other cluster slots than the record, so they are updated one by one
+        // instead of in a transaction.
+        for index_key in self.index_keys(value) {
+            let _: i32 = connection.sadd(index_key, &key_hash).await?;
+        }
+
+        let _: i32 = connection.zadd(self.hash_index_key(), &key_hash, 0).await?;
+
+        Ok(key.to_string())
+    }
+
+    async fn retrieve_api_key(&self, key: &str) -> Result<ApiKey, ApiKeyStorageError> {
+        match self.get_record(&ApiKey::hash_key(key)).await? {
+            Some(api_key) => Ok(api_key),
+            None => Err(ApiKeyStorageError::KeyNotFound),
+        }
+    }
+
+    async fn delete_api_key(&mut self, key: &str) -> Result<bool, ApiKeyStorageError> {
+        let mut connection = self.connection.clone();
+
+        let key_hash = ApiKey::hash_key(key);
+
+        let Some(api_key) = self.get_record(&key_hash).await? else {
+            return Ok(false);
+        };
+
+        let deleted: i32 = connection.del(self.record_key(&key_hash)).await?;
+
+        for index_key in self.index_keys(&api_key) {
+            let _: i32 = connection.srem(index_key, &key_hash).await?;
+        }
+
+        let _: i32 = connection.zrem(self.hash_index_key(), &key_hash).await?;
+
+        Ok(deleted > 0)
+    }
+}
+
+impl From<RedisError> for ApiKeyStorageError {
+    fn from(error: RedisError) -> Self {
+        ApiKeyStorageError::StorageError(error.into())
+    }
+}
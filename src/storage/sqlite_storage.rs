@@ -0,0 +1,184 @@
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqlitePoolOptions, SqliteRow},
+    types::Json,
+    Row,
+};
+
+use crate::{
+    errors::ApiKeyStorageError,
+    schema::CURRENT_SCHEMA_VERSION,
+    traits::{ApiKeyLister, ApiKeyStorage},
+    types::{ApiKey, ApiKeyLimits, ApiKeyPage, ApiKeyRestrictions, ApiKeyStatus},
+};
+
+const SCHEMA: [&str; 3] = [
+    "CREATE TABLE IF NOT EXISTS api_keys (
+        key_hash TEXT NOT NULL PRIMARY KEY,
+        key TEXT NOT NULL,
+        limits TEXT NOT NULL,
+        restrictions TEXT NOT NULL,
+        status TEXT NOT NULL,
+        owner TEXT,
+        created_at TEXT NOT NULL,
+        updated_at TEXT NOT NULL
+    )",
+    "CREATE INDEX IF NOT EXISTS api_keys_status_idx ON api_keys (status)",
+    "CREATE INDEX IF NOT EXISTS api_keys_owner_idx ON api_keys (owner)",
+];
+
+/// Stores API keys in a SQLite database file, or in memory when opened with `:memory:`.
+///
+/// The schema is created when the storage is opened and file databases use WAL journaling so
+/// that readers are not blocked by writers.
+#[derive(Clone)]
+pub struct SqliteStorage {
+    pool: SqlitePool,
+}
+
+impl SqliteStorage {
+    pub async fn new(path: &str) -> Result<Self, sqlx::Error> {
+        let pool = match path {
+            // Every connection to `:memory:` opens a database of its own, so the pool must keep
+            // exactly one connection alive for as long as the storage exists.
+            ":memory:" => {
+                SqlitePoolOptions::new()
+                    .min_connections(1)
+                    .max_connections(1)
+                    .idle_timeout(None)
+                    .max_lifetime(None)
+                    .connect_with(SqliteConnectOptions::from_str("sqlite::memory:")?)
+                    .await?
+            }
+            path => {
+                let options = SqliteConnectOptions::new()
+                    .filename(path)
+                    .create_if_missing(true)
+                    .journal_mode(SqliteJournalMode::Wal);
+
+                SqlitePoolOptions::new().connect_with(options).await?
+            }
+        };
+
+        Self::create_schema(&pool).await?;
+
+        Ok(Self { pool })
+    }
+
+    async fn create_schema(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+        let has_table: bool =
+            sqlx::query_scalar("SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = 'api_keys'")
+                .fetch_one(pool)
+                .await?;
+
+        // Databases created before keys had an owner need the column before the owner index can be created.
+        if has_table {
+            let has_owner: bool =
+                sqlx::query_scalar("SELECT COUNT(*) > 0 FROM pragma_table_info('api_keys') WHERE name = 'owner'")
+                    .fetch_one(pool)
+                    .await?;
+
+            if !has_owner {
+                sqlx::query("ALTER TABLE api_keys ADD COLUMN owner TEXT").execute(pool).await?;
+            }
+        }
+
+        for statement in SCHEMA {
+            sqlx::query(statement).execute(pool).await?;
+        }
+
+        Ok(())
+    }
+
+    fn api_key_from_row(row: SqliteRow) -> Result<ApiKey, ApiKeyStorageError> {
+        let status: String = row.try_get("status")?;
+
+        Ok(ApiKey {
+            key: row.try_get("key")?,
+            limits: row.try_get::<Json<ApiKeyLimits>, _>("limits")?.0,
+            restrictions: row.try_get::<Json<ApiKeyRestrictions>, _>("restrictions")?.0,
+            status: status.parse::<ApiKeyStatus>().map_err(|e| ApiKeyStorageError::SerializationError(e.into()))?,
+            owner: row.try_get("owner")?,
+            created_at: row.try_get::<DateTime<Utc>, _>("created_at")?,
+            updated_at: row.try_get::<DateTime<Utc>, _>("updated_at")?,
+            // Columns are upgraded by the migrations, so rows are always in the current shape.
+            schema_version: CURRENT_SCHEMA_VERSION,
+        })
+    }
+}
+
+#[async_trait]
+impl ApiKeyStorage for SqliteStorage {
+    async fn store_api_key(&mut self, key: &str, value: &ApiKey) -> Result<String, ApiKeyStorageError> {
+        let result = sqlx::query(
+            "INSERT INTO api_keys (key_hash, key, limits, restrictions, status, owner, created_at, updated_at) \
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?) \
+             ON CONFLICT (key_hash) DO NOTHING",
+        )
+        .bind(ApiKey::hash_key(key))
+        .bind(key)
+        .bind(Json(&value.limits))
+        .bind(Json(&value.restrictions))
+        .bind(value.status.to_string())
+        .bind(&value.owner)
+        .bind(value.created_at)
+        .bind(value.updated_at)
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(ApiKeyStorageError::KeyAlreadyExists);
+        }
+
+        Ok(key.to_string())
+    }
+
+    async fn retrieve_api_key(&self, key: &str) -> Result<ApiKey, ApiKeyStorageError> {
+        let row = sqlx::query(
+            "SELECT key, limits, restrictions, status, owner, created_at, updated_at FROM api_keys WHERE key_hash = ?",
+        )
+        .bind(ApiKey::hash_key(key))
+        .fetch_optional(&self.pool)
+        .await?;
+
+        match row {
+            Some(row) => Self::api_key_from_row(row),
+            None => Err(ApiKeyStorageError::KeyNotFound),
+        }
+    }
+
+    async fn delete_api_key(&mut self, key: &str) -> Result<bool, ApiKeyStorageError> {
+        let result = sqlx::query("DELETE FROM api_keys WHERE key_hash = ?")
+            .bind(ApiKey::hash_key(key))
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+#[async_trait]
+impl ApiKeyLister for SqliteStorage {
+    async fn list_api_keys(&self, cursor: Option<&str>, limit: usize) -> Result<ApiKeyPage, ApiKeyStorageError> {
+        let rows = sqlx::query(
+            "SELECT key_hash, key, limits, restrictions, status, owner, created_at, updated_at FROM api_keys \
+             WHERE key_hash > ? ORDER BY key_hash LIMIT ?",
+        )
+        .bind(cursor.unwrap_or_default())
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let next_cursor = match rows.len() == limit {
+            true => rows.last().map(|row| row.try_get::<String, _>("key_hash")).transpose()?,
+            false => None,
+        };
+
+        let api_keys = rows.into_iter().map(Self::api_key_from_row).collect::<Result<_, _>>()?;
+
+        Ok(ApiKeyPage { api_keys, next_cursor })
+    }
+}
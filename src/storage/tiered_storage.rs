@@ -0,0 +1,140 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use tokio::sync::RwLock;
+
+use crate::{
+    clock::Clock,
+    errors::{ApiKeyStorageError, ErrorChain},
+    traits::{ApiKeyLister, ApiKeyStorage},
+    types::{ApiKey, ApiKeyPage},
+};
+
+/// Serves keys from a fast tier (memory, Redis) in front of a durable tier (MongoDB, Postgres) that
+/// remains the source of truth.
+///
+/// Reads go to the fast tier first and fall back to the durable tier, copying the keys they find
+/// into the fast tier. Writes go to the durable tier first, then to the fast tier. Keys already in
+/// the fast tier keep being served while the durable tier is down.
+///
+/// The fast tier must share its contents between clones, like every storage of this crate does. It
+/// is never expired by `TieredStorage`: keys changed in the durable tier by other means stay in the
+/// fast tier until they are deleted through `TieredStorage`.
+///
+/// A key deleted while it is being read is not copied back to the fast tier by that read. This
+/// only covers reads made through the same `TieredStorage` or its clones: when several nodes share
+/// a fast tier, a read racing with a delete made by another node can still copy the key back.
+#[derive(Clone)]
+pub struct TieredStorage<Fast, Durable>
+where
+    Fast: ApiKeyStorage + Clone + Send + Sync,
+    Durable: ApiKeyStorage + Send + Sync,
+{
+    fast: Fast,
+    durable: Durable,
+    // Bumped by every delete. Copies to the fast tier are made while holding the read side and are
+    // dropped if a delete happened since the durable tier was read.
+    deletions: Arc<RwLock<u64>>,
+}
+
+impl<Fast, Durable> TieredStorage<Fast, Durable>
+where
+    Fast: ApiKeyStorage + Clone + Send + Sync,
+    Durable: ApiKeyStorage + Send + Sync,
+{
+    pub fn new(fast: Fast, durable: Durable) -> Self {
+        Self { fast, durable, deletions: Arc::default() }
+    }
+
+    /// Replaces whatever the fast tier holds for `key`, unless the key may have been deleted since
+    /// `deletions` was read. Failures are only logged, the next read repopulates the fast tier.
+    async fn populate(&self, key: &str, value: &ApiKey, deletions: u64) {
+        let current = self.deletions.read().await;
+
+        if *current != deletions {
+            return;
+        }
+
+        let mut fast = self.fast.clone();
+
+        if let Err(e) = fast.delete_api_key(key).await {
+            tracing::warn!("Unable to replace api key {} in the fast tier: {}", ApiKey::hash_key(key), ErrorChain(&e));
+            return;
+        }
+
+        match fast.store_api_key(key, value).await {
+            // Populated concurrently by another read.
+            Ok(_) | Err(ApiKeyStorageError::KeyAlreadyExists) => {}
+            Err(e) => {
+                tracing::warn!("Unable to copy api key {} to the fast tier: {}", ApiKey::hash_key(key), ErrorChain(&e))
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<Fast, Durable> ApiKeyStorage for TieredStorage<Fast, Durable>
+where
+    Fast: ApiKeyStorage + Clone + Send + Sync,
+    Durable: ApiKeyStorage + Send + Sync,
+{
+    async fn store_api_key(&mut self, key: &str, value: &ApiKey) -> Result<String, ApiKeyStorageError> {
+        let deletions = *self.deletions.read().await;
+        let stored = self.durable.store_api_key(key, value).await?;
+
+        self.populate(key, value, deletions).await;
+
+        Ok(stored)
+    }
+
+    async fn retrieve_api_key(&self, key: &str) -> Result<ApiKey, ApiKeyStorageError> {
+        match self.fast.retrieve_api_key(key).await {
+            Ok(api_key) => return Ok(api_key),
+            Err(ApiKeyStorageError::KeyNotFound) => {}
+            Err(e) => {
+                tracing::warn!(
+                    "Unable to read api key {} from the fast tier: {}",
+                    ApiKey::hash_key(key),
+                    ErrorChain(&e)
+                )
+            }
+        }
+
+        let deletions = *self.deletions.read().await;
+        let api_key = self.durable.retrieve_api_key(key).await?;
+
+        self.populate(key, &api_key, deletions).await;
+
+        Ok(api_key)
+    }
+
+    /// A key that could not be removed from the fast tier would keep being served, so unlike the
+    /// other writes a failure of the fast tier fails the delete.
+    async fn delete_api_key(&mut self, key: &str) -> Result<bool, ApiKeyStorageError> {
+        let deleted = self.durable.delete_api_key(key).await?;
+
+        let mut deletions = self.deletions.write().await;
+        *deletions += 1;
+
+        let evicted = self.fast.delete_api_key(key).await?;
+
+        Ok(deleted || evicted)
+    }
+
+    fn use_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.fast.use_clock(clock.clone());
+        self.durable.use_clock(clock);
+    }
+}
+
+/// Keys are listed from the durable tier.
+#[async_trait]
+impl<Fast, Durable> ApiKeyLister for TieredStorage<Fast, Durable>
+where
+    Fast: ApiKeyStorage + Clone + Send + Sync,
+    Durable: ApiKeyStorage + ApiKeyLister + Send + Sync,
+{
+    async fn list_api_keys(&self, cursor: Option<&str>, limit: usize) -> Result<ApiKeyPage, ApiKeyStorageError> {
+        self.durable.list_api_keys(cursor, limit).await
+    }
+}
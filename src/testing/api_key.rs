@@ -0,0 +1,82 @@
+use chrono::{DateTime, Utc};
+
+use crate::{
+    schema::CURRENT_SCHEMA_VERSION,
+    types::{ApiKey, ApiKeyLimit, ApiKeyLimits, ApiKeyRestrictions, ApiKeyStatus},
+};
+
+/// An active key allowing 100 reads and 100 writes per minute from `example.com`, without limit on
+/// concurrent requests.
+pub fn test_api_key(key: &str) -> ApiKey {
+    TestApiKey::new(key).build()
+}
+
+/// Builds keys for tests, starting from the key returned by `test_api_key`.
+#[derive(Debug, Clone)]
+pub struct TestApiKey {
+    api_key: ApiKey,
+}
+
+impl TestApiKey {
+    pub fn new(key: impl Into<String>) -> Self {
+        let now = Utc::now();
+
+        Self {
+            api_key: ApiKey {
+                key: key.into(),
+                limits: ApiKeyLimits {
+                    max_reads_per_minute: ApiKeyLimit::Limited(100),
+                    max_writes_per_minute: ApiKeyLimit::Limited(100),
+                    max_concurrent_requests: ApiKeyLimit::Unlimited,
+                },
+                restrictions: ApiKeyRestrictions { allowed_domains: vec!["example.com".to_string()] },
+                status: ApiKeyStatus::Active,
+                owner: None,
+                created_at: now,
+                updated_at: now,
+                schema_version: CURRENT_SCHEMA_VERSION,
+            },
+        }
+    }
+
+    pub fn with_reads_per_minute(mut self, limit: ApiKeyLimit) -> Self {
+        self.api_key.limits.max_reads_per_minute = limit;
+        self
+    }
+
+    pub fn with_writes_per_minute(mut self, limit: ApiKeyLimit) -> Self {
+        self.api_key.limits.max_writes_per_minute = limit;
+        self
+    }
+
+    pub fn with_concurrent_requests(mut self, limit: ApiKeyLimit) -> Self {
+        self.api_key.limits.max_concurrent_requests = limit;
+        self
+    }
+
+    pub fn with_allowed_domains(mut self, allowed_domains: Vec<String>) -> Self {
+        self.api_key.restrictions.allowed_domains = allowed_domains;
+        self
+    }
+
+    pub fn with_status(mut self, status: ApiKeyStatus) -> Self {
+        self.api_key.status = status;
+        self
+    }
+
+    pub fn with_owner(mut self, owner: impl Into<String>) -> Self {
+        self.api_key.owner = Some(owner.into());
+        self
+    }
+
+    /// Sets both `created_at` and `updated_at`, e.g. to the time of a `ManualClock`.
+    pub fn with_created_at(mut self, created_at: DateTime<Utc>) -> Self {
+        self.api_key.created_at = created_at;
+        self.api_key.updated_at = created_at;
+        self
+    }
+
+    pub fn build(self) -> ApiKey {
+        self.api_key
+    }
+}
@@ -0,0 +1,130 @@
+use std::sync::{
+    atomic::{AtomicI64, AtomicUsize, Ordering},
+    Arc, Mutex, MutexGuard,
+};
+
+use async_trait::async_trait;
+
+use crate::{
+    errors::ApiKeyLimiterError,
+    traits::ApiKeyLimiter,
+    types::{ApiKey, Lease},
+};
+
+#[derive(Debug, Clone)]
+enum Outcome {
+    Accept,
+    RateLimitExceeded,
+    ConcurrencyLimitExceeded,
+    Fail(String),
+}
+
+/// Limiter whose answers are set by the test, and which keeps track of the units it was charged.
+///
+/// It accepts every key until told otherwise. While failing, every call returns
+/// `ApiKeyLimiterError::Other`, like a limiter whose backend is down. Clones share the same state.
+#[derive(Clone)]
+pub struct MockLimiter {
+    outcome: Arc<Mutex<Outcome>>,
+    uses: Arc<AtomicUsize>,
+    used: Arc<AtomicI64>,
+    releases: Arc<AtomicUsize>,
+}
+
+impl Default for MockLimiter {
+    fn default() -> Self {
+        Self {
+            outcome: Arc::new(Mutex::new(Outcome::Accept)),
+            uses: Arc::default(),
+            used: Arc::default(),
+            releases: Arc::default(),
+        }
+    }
+}
+
+impl MockLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn accept(&self) {
+        *self.outcome() = Outcome::Accept;
+    }
+
+    pub fn force_rate_limit(&self) {
+        *self.outcome() = Outcome::RateLimitExceeded;
+    }
+
+    pub fn force_concurrency_limit(&self) {
+        *self.outcome() = Outcome::ConcurrencyLimitExceeded;
+    }
+
+    pub fn force_error(&self, message: impl Into<String>) {
+        *self.outcome() = Outcome::Fail(message.into());
+    }
+
+    /// Number of calls to `use_key`, accepted or not.
+    pub fn uses(&self) -> usize {
+        self.uses.load(Ordering::SeqCst)
+    }
+
+    /// Units currently charged: accepted uses and charges, minus refunds.
+    pub fn used(&self) -> i64 {
+        self.used.load(Ordering::SeqCst)
+    }
+
+    pub fn releases(&self) -> usize {
+        self.releases.load(Ordering::SeqCst)
+    }
+
+    fn outcome(&self) -> MutexGuard<'_, Outcome> {
+        match self.outcome.lock() {
+            Ok(outcome) => outcome,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    fn check_available(&self) -> Result<(), ApiKeyLimiterError> {
+        match &*self.outcome() {
+            Outcome::Fail(message) => Err(ApiKeyLimiterError::Other(message.clone().into())),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[async_trait]
+impl ApiKeyLimiter for MockLimiter {
+    async fn use_key(&self, _api_key: &ApiKey) -> Result<(), ApiKeyLimiterError> {
+        self.uses.fetch_add(1, Ordering::SeqCst);
+
+        let outcome = self.outcome().clone();
+
+        match outcome {
+            Outcome::Accept => {
+                self.used.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            }
+            Outcome::RateLimitExceeded => Err(ApiKeyLimiterError::RateLimitExceeded),
+            Outcome::ConcurrencyLimitExceeded => Err(ApiKeyLimiterError::ConcurrencyLimitExceeded),
+            Outcome::Fail(message) => Err(ApiKeyLimiterError::Other(message.into())),
+        }
+    }
+
+    async fn charge_key(&self, _api_key: &ApiKey, _lease: &Lease, units: u32) -> Result<(), ApiKeyLimiterError> {
+        self.check_available()?;
+        self.used.fetch_add(units as i64, Ordering::SeqCst);
+        Ok(())
+    }
+
+    async fn refund_key(&self, _api_key: &ApiKey, _lease: &Lease, units: u32) -> Result<(), ApiKeyLimiterError> {
+        self.check_available()?;
+        self.used.fetch_sub(units as i64, Ordering::SeqCst);
+        Ok(())
+    }
+
+    async fn release_key(&self, _api_key: &ApiKey, _lease: &Lease) -> Result<(), ApiKeyLimiterError> {
+        self.check_available()?;
+        self.releases.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+}
@@ -0,0 +1,9 @@
+//! Fixtures for testing code built on this crate: keys, a recording storage, a scriptable limiter
+//! and requests against routers wrapped in `ApiKeyLayer`. Enabled by the `testing` feature, which is
+//! meant to be turned on for dev-dependencies only.
+
+pub mod api_key;
+pub mod limiter;
+#[cfg(feature = "axum")]
+pub mod request;
+pub mod storage;
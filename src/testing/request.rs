@@ -0,0 +1,49 @@
+use std::convert::Infallible;
+
+use axum::{body::Body, extract::Request, response::Response, Router};
+use tower::ServiceExt;
+
+use crate::{
+    axum_layer::{ApiKeyLayer, API_KEY_HEADER},
+    manager::KeyManager,
+    storage::memory_storage::HashMapStorage,
+    traits::{ApiKeyLimiter, ApiKeyStorage},
+    types::ApiKey,
+};
+
+/// Wraps `router` in an `ApiKeyLayer` serving `api_keys` from memory and limiting them with `limiter`.
+pub async fn router_with_keys<L>(router: Router, api_keys: impl IntoIterator<Item = ApiKey>, limiter: L) -> Router
+where
+    L: ApiKeyLimiter + Send + Sync + Clone + 'static,
+{
+    let mut storage = HashMapStorage::new();
+
+    for api_key in api_keys {
+        if let Err(e) = storage.store_api_key(&api_key.key, &api_key).await {
+            panic!("Unable to store test api key {}: {e}", api_key.key_hash());
+        }
+    }
+
+    router.layer(ApiKeyLayer::new(KeyManager::new(storage, limiter)))
+}
+
+/// A `GET` request to `uri` carrying `key` the way `ApiKeyLayer` expects it.
+pub fn request_with_key(uri: &str, key: &str) -> Request {
+    match Request::get(uri).header(API_KEY_HEADER, key).body(Body::empty()) {
+        Ok(request) => request,
+        Err(e) => panic!("Invalid test request to {uri}: {e}"),
+    }
+}
+
+pub async fn send(router: Router, request: Request) -> Response {
+    let response: Result<Response, Infallible> = router.oneshot(request).await;
+
+    match response {
+        Ok(response) => response,
+        Err(e) => match e {},
+    }
+}
+
+pub async fn send_with_key(router: Router, uri: &str, key: &str) -> Response {
+    send(router, request_with_key(uri, key)).await
+}
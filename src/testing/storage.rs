@@ -0,0 +1,101 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex, MutexGuard,
+};
+
+use async_trait::async_trait;
+
+use crate::{
+    errors::ApiKeyStorageError,
+    storage::memory_storage::HashMapStorage,
+    traits::{ApiKeyLister, ApiKeyStorage},
+    types::{ApiKey, ApiKeyPage},
+};
+
+/// A call made to a `RecordingStorage`, with the key it was made for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StorageCall {
+    Store(String),
+    Retrieve(String),
+    Delete(String),
+}
+
+/// In-memory storage which records the calls it receives and can be taken down. Clones share the
+/// same keys and records.
+#[derive(Clone)]
+pub struct RecordingStorage {
+    inner: HashMapStorage,
+    calls: Arc<Mutex<Vec<StorageCall>>>,
+    available: Arc<AtomicBool>,
+}
+
+impl Default for RecordingStorage {
+    fn default() -> Self {
+        Self { inner: HashMapStorage::new(), calls: Arc::default(), available: Arc::new(AtomicBool::new(true)) }
+    }
+}
+
+impl RecordingStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn calls(&self) -> Vec<StorageCall> {
+        self.lock_calls().clone()
+    }
+
+    pub fn retrievals(&self) -> usize {
+        self.lock_calls().iter().filter(|call| matches!(call, StorageCall::Retrieve(_))).count()
+    }
+
+    pub fn clear_calls(&self) {
+        self.lock_calls().clear();
+    }
+
+    /// While unavailable, every call fails with `ApiKeyStorageError::StorageError`.
+    pub fn set_available(&self, available: bool) {
+        self.available.store(available, Ordering::SeqCst);
+    }
+
+    fn lock_calls(&self) -> MutexGuard<'_, Vec<StorageCall>> {
+        match self.calls.lock() {
+            Ok(calls) => calls,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    fn record(&self, call: StorageCall) -> Result<(), ApiKeyStorageError> {
+        self.lock_calls().push(call);
+
+        match self.available.load(Ordering::SeqCst) {
+            true => Ok(()),
+            false => Err(ApiKeyStorageError::StorageError("storage unavailable".into())),
+        }
+    }
+}
+
+#[async_trait]
+impl ApiKeyStorage for RecordingStorage {
+    async fn store_api_key(&mut self, key: &str, value: &ApiKey) -> Result<String, ApiKeyStorageError> {
+        self.record(StorageCall::Store(key.to_string()))?;
+        self.inner.store_api_key(key, value).await
+    }
+
+    async fn retrieve_api_key(&self, key: &str) -> Result<ApiKey, ApiKeyStorageError> {
+        self.record(StorageCall::Retrieve(key.to_string()))?;
+        self.inner.retrieve_api_key(key).await
+    }
+
+    async fn delete_api_key(&mut self, key: &str) -> Result<bool, ApiKeyStorageError> {
+        self.record(StorageCall::Delete(key.to_string()))?;
+        self.inner.delete_api_key(key).await
+    }
+}
+
+/// Listing is not recorded.
+#[async_trait]
+impl ApiKeyLister for RecordingStorage {
+    async fn list_api_keys(&self, cursor: Option<&str>, limit: usize) -> Result<ApiKeyPage, ApiKeyStorageError> {
+        self.inner.list_api_keys(cursor, limit).await
+    }
+}
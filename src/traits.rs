@@ -1,8 +1,11 @@
+use std::sync::Arc;
+
 use async_trait::async_trait;
 
 use crate::{
+    clock::Clock,
     errors::{ApiKeyLimiterError, ApiKeyManagerError, ApiKeyStorageError},
-    types::ApiKey,
+    types::{ApiKey, ApiKeyPage, Lease, RateLimitStatus},
 };
 
 #[async_trait]
@@ -10,15 +13,86 @@ pub trait ApiKeyStorage {
     async fn store_api_key(&mut self, key: &str, value: &ApiKey) -> Result<String, ApiKeyStorageError>;
     async fn retrieve_api_key(&self, key: &str) -> Result<ApiKey, ApiKeyStorageError>;
     async fn delete_api_key(&mut self, key: &str) -> Result<bool, ApiKeyStorageError>;
+
+    /// Makes the storage read the time from `clock` (see `KeyManager::with_clock`). Storages that do
+    /// not depend on the time can rely on the default no-op.
+    fn use_clock(&mut self, _clock: Arc<dyn Clock>) {}
+}
+
+/// Storages able to enumerate their keys, e.g. to export them (see `backup`).
+#[async_trait]
+pub trait ApiKeyLister {
+    /// Returns up to `limit` keys following `cursor`, which is `None` for the first page and then
+    /// the `next_cursor` of the previous page. Keys stored while listing may or may not be returned.
+    async fn list_api_keys(&self, cursor: Option<&str>, limit: usize) -> Result<ApiKeyPage, ApiKeyStorageError>;
 }
 
 #[async_trait]
 pub trait ApiKeyLimiter {
+    /// Counts a use of the key. No in-flight slot is held afterwards: concurrency limiters only check
+    /// that one is free.
     async fn use_key(&self, api_key: &ApiKey) -> Result<(), ApiKeyLimiterError>;
+
+    /// Same as `use_key`, except that concurrency limiters hold a slot until `release_key` is called
+    /// with the same `lease`. Limiters that do not hold a slot per request can rely on the default,
+    /// which calls `use_key`.
+    async fn acquire_key(&self, api_key: &ApiKey, _lease: &Lease) -> Result<(), ApiKeyLimiterError> {
+        self.use_key(api_key).await
+    }
+
+    /// Charges `units` of extra usage to the request of `lease` after it has been served.
+    /// Limiters that do not count usage can rely on the default no-op.
+    async fn charge_key(&self, _api_key: &ApiKey, _lease: &Lease, _units: u32) -> Result<(), ApiKeyLimiterError> {
+        Ok(())
+    }
+
+    /// Gives back `units` previously consumed by the request of `lease`.
+    async fn refund_key(&self, _api_key: &ApiKey, _lease: &Lease, _units: u32) -> Result<(), ApiKeyLimiterError> {
+        Ok(())
+    }
+
+    /// Ends a request started by `acquire_key`, freeing any in-flight slot it was holding.
+    async fn release_key(&self, _api_key: &ApiKey, _lease: &Lease) -> Result<(), ApiKeyLimiterError> {
+        Ok(())
+    }
+
+    /// Current usage of the rate limit of a key. Limiters that do not enforce a rate limit, and
+    /// unlimited keys, have none.
+    async fn rate_limit_status(&self, _api_key: &ApiKey) -> Result<Option<RateLimitStatus>, ApiKeyLimiterError> {
+        Ok(None)
+    }
+
+    /// Makes the limiter read the time from `clock` (see `KeyManager::with_clock`). Limiters that do
+    /// not depend on the time can rely on the default no-op.
+    fn use_clock(&mut self, _clock: Arc<dyn Clock>) {}
 }
 
 #[async_trait]
 pub trait ApiKeyManager {
     async fn get_key(&self, key: &str) -> Result<ApiKey, ApiKeyManagerError>;
-    async fn use_key(&self, key: &str) -> Result<(), ApiKeyManagerError>;
+
+    /// Counts a use of the key, without holding an in-flight slot (see `ApiKeyLimiter::use_key`).
+    async fn use_key(&self, key: &str) -> Result<ApiKey, ApiKeyManagerError>;
+
+    /// Same as `use_key`, holding an in-flight slot until `release_key` is called with the returned
+    /// lease.
+    async fn acquire_key(&self, key: &str) -> Result<(ApiKey, Lease), ApiKeyManagerError> {
+        Ok((self.use_key(key).await?, Lease::new()))
+    }
+
+    /// Charges `units` of extra usage to the request of `lease` after it has been served. Managers
+    /// that do not count usage can rely on the default no-op.
+    async fn charge_key(&self, _api_key: &ApiKey, _lease: &Lease, _units: u32) -> Result<(), ApiKeyManagerError> {
+        Ok(())
+    }
+
+    /// Gives back `units` previously consumed by the request of `lease`.
+    async fn refund_key(&self, _api_key: &ApiKey, _lease: &Lease, _units: u32) -> Result<(), ApiKeyManagerError> {
+        Ok(())
+    }
+
+    /// Ends a request started by `acquire_key`, freeing any in-flight slot it was holding.
+    async fn release_key(&self, _api_key: &ApiKey, _lease: &Lease) -> Result<(), ApiKeyManagerError> {
+        Ok(())
+    }
 }
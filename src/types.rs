@@ -1,11 +1,16 @@
-use std::fmt;
+use std::{fmt, str::FromStr};
 
 use chrono::{DateTime, Utc};
+use rand::{distr::Alphanumeric, Rng};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+use crate::builder::ApiKeyBuilder;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub enum ApiKeyLimit {
     Limited(u32),
+    #[default]
     Unlimited,
 }
 
@@ -22,9 +27,13 @@ impl fmt::Display for ApiKeyLimit {
 pub struct ApiKeyLimits {
     pub max_reads_per_minute: ApiKeyLimit,
     pub max_writes_per_minute: ApiKeyLimit,
+    /// Requests that may be in flight at the same time. Keys stored before this limit existed
+    /// are treated as unlimited.
+    #[serde(default)]
+    pub max_concurrent_requests: ApiKeyLimit,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct ApiKeyRestrictions {
     pub allowed_domains: Vec<String>,
 }
@@ -36,12 +45,115 @@ pub enum ApiKeyStatus {
     Deleted,
 }
 
+impl fmt::Display for ApiKeyStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiKeyStatus::Active => write!(f, "Active"),
+            ApiKeyStatus::Inactive => write!(f, "Inactive"),
+            ApiKeyStatus::Deleted => write!(f, "Deleted"),
+        }
+    }
+}
+
+impl FromStr for ApiKeyStatus {
+    type Err = String;
+
+    fn from_str(status: &str) -> Result<Self, Self::Err> {
+        match status {
+            "Active" => Ok(ApiKeyStatus::Active),
+            "Inactive" => Ok(ApiKeyStatus::Inactive),
+            "Deleted" => Ok(ApiKeyStatus::Deleted),
+            status => Err(format!("Unknown api key status {status}")),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ApiKey {
     pub key: String,
     pub limits: ApiKeyLimits,
     pub restrictions: ApiKeyRestrictions,
     pub status: ApiKeyStatus,
+    /// Account or service the key was issued to.
+    #[serde(default)]
+    pub owner: Option<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// Shape of the stored record, see `schema`. Records stored before versioning read as 0.
+    #[serde(default)]
+    pub schema_version: u32,
+}
+
+impl ApiKey {
+    /// Starts a new key, see `ApiKeyBuilder` for the defaults.
+    pub fn builder() -> ApiKeyBuilder {
+        ApiKeyBuilder::new()
+    }
+
+    /// Hex encoded SHA-256 of an API key secret. Safe to use wherever the key has to be identified
+    /// without being revealed, e.g. in Redis key names or logs.
+    pub fn hash_key(key: &str) -> String {
+        format!("{:x}", Sha256::digest(key.as_bytes()))
+    }
+
+    pub fn key_hash(&self) -> String {
+        Self::hash_key(&self.key)
+    }
+}
+
+/// Usage of the rate limit of a key, e.g. to fill `RateLimit-*` response headers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RateLimitStatus {
+    pub limit: u32,
+    pub remaining: u32,
+    /// When the current window ends and the usage goes back to zero.
+    pub reset_at: DateTime<Utc>,
+}
+
+/// Identifies a request between `ApiKeyLimiter::acquire_key` and `ApiKeyLimiter::release_key`, so
+/// that limiters holding a slot per request free the slot of that request and no other.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lease {
+    id: String,
+    // Set by `KeyManager`, so that the request is charged and ended by the limiter that admitted it.
+    pub(crate) admitted_by: AdmittedBy,
+}
+
+/// Which limiter of a `KeyManager` admitted a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) enum AdmittedBy {
+    #[default]
+    Limiter,
+    /// The fallback limiter of `FailurePolicy::Fallback`.
+    Fallback,
+    /// No limiter, under `FailurePolicy::FailOpen`.
+    Nobody,
+}
+
+impl Default for Lease {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Lease {
+    /// A new lease with a random id.
+    pub fn new() -> Self {
+        Self {
+            id: rand::rng().sample_iter(&Alphanumeric).take(32).map(char::from).collect(),
+            admitted_by: AdmittedBy::default(),
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+/// A page of keys returned by `ApiKeyLister::list_api_keys`.
+#[derive(Debug, Clone, Default)]
+pub struct ApiKeyPage {
+    pub api_keys: Vec<ApiKey>,
+    /// Opaque position after the last key of the page, `None` once every key has been listed.
+    pub next_cursor: Option<String>,
 }